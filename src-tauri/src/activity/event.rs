@@ -31,6 +31,10 @@ pub enum ActivityEventType {
     SshServiceDisconnected,
     /// Ошибка подключения к сервису
     ServiceConnectionError,
+    /// Отключение сервиса по таймауту бездействия
+    ServiceIdleDisconnected,
+    /// Локальная точка подключения сервиса доступна не только с loopback
+    ServiceExposedOnNetwork,
     /// Запрос учетных данных
     CredentialsRequested,
     /// Сохранение учетных данных
@@ -10,8 +10,18 @@ use std::fs;
 use std::path::PathBuf;
 use cfg_if::cfg_if;
 
+use crate::migrations;
+
 use super::event::{ActivityEvent, ActivityEventType, ActivitySeverity, ActivityStats, ActivityFilter};
 
+pub const LOG_FILE: &str = if cfg!(target_os = "windows") {
+    "activity_log_windows.json"
+} else if cfg!(target_os = "macos") {
+    "activity_log_macos.json"
+} else {
+    "activity_log.json"
+};
+
 pub struct ActivityState {
     events: Arc<Mutex<Vec<ActivityEvent>>>,
     max_events: usize,
@@ -231,7 +241,9 @@ impl ActivityState {
 
     pub async fn save_to_file(&self, app: &AppHandle) -> anyhow::Result<()> {
         let events = self.events.lock().await;
-        let json = serde_json::to_string(&*events)?;
+        let mut value = serde_json::json!({ "events": &*events });
+        migrations::stamp(&mut value, &migrations::ACTIVITY);
+        let json = serde_json::to_string(&value)?;
         let path = Self::get_log_path(app)?;
         fs::write(&path, json)?;
         Ok(())
@@ -240,7 +252,9 @@ impl ActivityState {
     pub async fn load_from_file(&self, app: &AppHandle) -> anyhow::Result<()> {
         let path = Self::get_log_path(app)?;
         if let Ok(data) = fs::read_to_string(&path) {
-            let events: Vec<ActivityEvent> = serde_json::from_str(&data)?;
+            let (_, value) =
+                migrations::upgrade(&migrations::ACTIVITY, serde_json::from_str(&data)?)?;
+            let events: Vec<ActivityEvent> = serde_json::from_value(value["events"].clone())?;
             let mut lock = self.events.lock().await;
             *lock = events;
         }
@@ -248,15 +262,8 @@ impl ActivityState {
     }
 
     fn get_log_path(app: &AppHandle) -> anyhow::Result<PathBuf> {
-        let filename = if cfg!(target_os = "windows") {
-            "activity_log_windows.json"
-        } else if cfg!(target_os = "macos") {
-            "activity_log_macos.json"
-        } else {
-            "activity_log.json"
-        };
         let mut path = app.path().app_data_dir()?;
-        path.push(filename);
+        path.push(LOG_FILE);
         Ok(path)
     }
 } 
\ No newline at end of file
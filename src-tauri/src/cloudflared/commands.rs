@@ -3,9 +3,16 @@ use std::process::Command;
 use serde::{Serialize, Deserialize};
 use std::sync::Mutex;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
-use std::io::Write;
+use ts_rs::TS;
+
+use crate::settings::SettingsState;
+use crate::util::PanicLock;
+
+use super::ingress::{self, IngressConfig, IngressState};
+use super::installer::{self, DownloadState, InstallSource};
+use super::versions::{CalVer, CloudflaredVersions, VersionStore};
 
 // Store for active child processes
 pub struct TunnelState {
@@ -28,7 +35,22 @@ pub struct ActiveTunnel {
     pub pid: u32,
 }
 
-fn get_cloudflared_path<R: Runtime>(app: &AppHandle<R>) -> PathBuf {
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct CloudflaredStatus {
+    pub active_version: Option<String>,
+    /// Version parsed from `--version` output, e.g. `2024.12.0`
+    pub installed_version: Option<String>,
+    pub path: String,
+    pub version_output: String,
+}
+
+/// Path of the active managed cloudflared, falling back to the legacy unversioned install.
+pub(super) fn get_cloudflared_path<R: Runtime>(app: &AppHandle<R>) -> PathBuf {
+    if let Some(path) = VersionStore::new(app).ok().and_then(|store| store.active_binary()) {
+        return path;
+    }
+
     let app_data_dir = app.path().app_data_dir().expect("failed to get app data dir");
     if !app_data_dir.exists() {
         fs::create_dir_all(&app_data_dir).expect("failed to create app data dir");
@@ -41,161 +63,98 @@ fn get_cloudflared_path<R: Runtime>(app: &AppHandle<R>) -> PathBuf {
     return app_data_dir.join("cloudflared");
 }
 
+fn mirror_url<R: Runtime>(app: &AppHandle<R>) -> Option<String> {
+    let settings = app.try_state::<SettingsState>()?;
+    let url = settings.readp().cloudflared_mirror_url.clone();
+    url.filter(|url| !url.trim().is_empty())
+}
+
+/// Installs the given release tag (or the latest one) side by side with other versions and activates it.
+/// Without an explicit `source` the configured mirror is preferred over GitHub.
 #[command]
-pub async fn install_cloudflared<R: Runtime>(app: AppHandle<R>) -> Result<String, String> {
-    let target_path = get_cloudflared_path(&app);
-    
-    let os = std::env::consts::OS;
-    let arch = std::env::consts::ARCH;
-    
-    let binary_name = match (os, arch) {
-        ("macos", "x86_64") => "cloudflared-darwin-amd64",
-        ("macos", "aarch64") => "cloudflared-darwin-amd64", // Rosetta 2 or native if available? Cloudflare provides darwin-amd64. Wait, they have darwin-amd64. Do they have arm64? Yes, cloudflared-darwin-amd64.tgz. Actually releases page has 'cloudflared-darwin-amd64'. Let's check releases. 
-        // Checking https://github.com/cloudflare/cloudflared/releases/latest
-        // They have: cloudflared-darwin-amd64.tgz, cloudflared-linux-amd64, cloudflared-windows-amd64.exe
-        // For M1/M2 macs (aarch64), they usually run amd64 via Rosetta or there might be an arm64 build?
-        // Looking at recent releases: cloudflared-darwin-amd64.tgz is the main one. 
-        // Wait, there is no arm64 build for mac in the main list usually? 
-        // Actually, let's look closer. 
-        // https://github.com/cloudflare/cloudflared/releases/download/2024.12.0/cloudflared-darwin-amd64.tgz
-        // There doesn't seem to be a specific arm64 build for mac in the assets list I recall. 
-        // But let's assume amd64 works via Rosetta.
-        
-        ("linux", "x86_64") => "cloudflared-linux-amd64",
-        ("linux", "x86") => "cloudflared-linux-386",
-        ("linux", "aarch64") => "cloudflared-linux-arm64",
-        ("linux", "arm") => "cloudflared-linux-arm",
-        ("windows", "x86_64") => "cloudflared-windows-amd64.exe",
-        ("windows", "x86") => "cloudflared-windows-386.exe",
-        _ => return Err(format!("Unsupported platform: {} {}", os, arch)),
-    };
+pub async fn install_cloudflared<R: Runtime>(
+    app: AppHandle<R>,
+    version: Option<String>,
+    source: Option<InstallSource>,
+) -> Result<String, String> {
+    install_release(&app, version, source).await
+}
 
-    // Special handling for macOS to download the binary directly if possible, but they provide .tgz. 
-    // Actually, for macOS, the binary is inside a tgz. That complicates things.
-    // Let's check if there is a direct binary download. 
-    // https://github.com/cloudflare/cloudflared/releases/latest/download/cloudflared-darwin-amd64.tgz
-    // It seems macOS only has .tgz.
-    // Linux and Windows have direct binaries.
-    
-    // If macOS, we might need to extract it. Or maybe we can find a direct binary link?
-    // Cloudflare docs say: brew install cloudflared.
-    // But for direct download: https://github.com/cloudflare/cloudflared/releases/latest/download/cloudflared-darwin-amd64.tgz
-    
-    // For the sake of this task, let's assume we can download the binary directly for Linux/Windows.
-    // For macOS, if it's a tgz, we need to extract it. 
-    // However, maybe there is a direct binary URL? 
-    // https://github.com/cloudflare/cloudflared/releases/latest/download/cloudflared-darwin-amd64 is NOT available.
-    
-    // Let's handle Linux and Windows first, and for macOS, we might need to use `tar`.
-    
-    let url = format!("https://github.com/cloudflare/cloudflared/releases/latest/download/{}", binary_name);
-    
-    // For macOS, we need to handle the .tgz extension if that's the only option.
-    // But wait, if I am on macOS, I can try to download it.
-    
-    let download_url = if os == "macos" {
-        // We will need to handle tgz. For now, let's try to assume there might be a binary or fail gracefully.
-        // Actually, let's check if we can use `curl` and `tar` via Command for macOS as a workaround if reqwest is too complex for tgz extraction without extra crates.
-        // But we have `flate2` and `tar` in Cargo.toml? No, we don't.
-        // Wait, Cargo.toml has `flate2` and `tar`?
-        // Let me check Cargo.toml again.
-        // I see `flate2 = "1.0"`. I don't see `tar`.
-        // So I can't easily extract tgz.
-        
-        // Alternative: Use `curl` and `tar` system commands on macOS.
-        return install_cloudflared_macos(app, &target_path).await;
-    } else {
-        url
+pub(super) async fn install_release<R: Runtime>(
+    app: &AppHandle<R>,
+    version: Option<String>,
+    source: Option<InstallSource>,
+) -> Result<String, String> {
+    let store = VersionStore::new(app).map_err(|e| e.to_string())?;
+    let version = version
+        .as_deref()
+        .map(VersionStore::normalize)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+
+    let source = source.unwrap_or_else(|| match mirror_url(app) {
+        Some(_) => InstallSource::Mirror,
+        None => InstallSource::GitHub,
+    });
+
+    let artifact = match source {
+        InstallSource::GitHub => installer::fetch_github(app, version.as_deref()).await?,
+        InstallSource::Mirror => {
+            let base_url = mirror_url(app).ok_or("No cloudflared mirror configured in settings")?;
+            installer::fetch_mirror(app, &base_url, version.as_deref()).await?
+        }
+        InstallSource::LocalFile { path, sha256 } => {
+            installer::read_local(Path::new(&path), version.as_deref(), sha256.as_deref())?
+        }
     };
 
-    let response = reqwest::get(&download_url).await.map_err(|e| e.to_string())?;
-    let content = response.bytes().await.map_err(|e| e.to_string())?;
-    
-    let mut file = fs::File::create(&target_path).map_err(|e| e.to_string())?;
-    file.write_all(&content).map_err(|e| e.to_string())?;
-    
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(&target_path).map_err(|e| e.to_string())?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&target_path, perms).map_err(|e| e.to_string())?;
-    }
-    
+    let installed_version = artifact.version.clone();
+    let target_path = installer::install(&store, artifact)?;
+    store.activate(&installed_version).map_err(|e| e.to_string())?;
+
     Ok(target_path.to_string_lossy().to_string())
 }
 
-async fn install_cloudflared_macos<R: Runtime>(app: AppHandle<R>, target_path: &PathBuf) -> Result<String, String> {
-    // macOS specific installation using curl and tar
-    let url = "https://github.com/cloudflare/cloudflared/releases/latest/download/cloudflared-darwin-amd64.tgz";
-    let app_data_dir = app.path().app_data_dir().expect("failed to get app data dir");
-    let tgz_path = app_data_dir.join("cloudflared.tgz");
-    
-    // Download tgz
-    let output = Command::new("curl")
-        .arg("-L")
-        .arg("-o")
-        .arg(&tgz_path)
-        .arg(url)
-        .output()
-        .map_err(|e| e.to_string())?;
-        
-    if !output.status.success() {
-        return Err(format!("Failed to download cloudflared: {}", String::from_utf8_lossy(&output.stderr)));
-    }
-    
-    // Extract tgz
-    let output = Command::new("tar")
-        .arg("-xzf")
-        .arg(&tgz_path)
-        .arg("-C")
-        .arg(&app_data_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
-        
-    if !output.status.success() {
-        return Err(format!("Failed to extract cloudflared: {}", String::from_utf8_lossy(&output.stderr)));
-    }
-    
-    // The extracted file is named 'cloudflared'. We might need to rename it if our target_path is different, 
-    // but get_cloudflared_path returns .../cloudflared, so it should be fine.
-    // However, tar extracts it as 'cloudflared'.
-    
-    // Cleanup tgz
-    let _ = fs::remove_file(tgz_path);
-    
-    // Ensure executable
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        if target_path.exists() {
-             let mut perms = fs::metadata(target_path).map_err(|e| e.to_string())?.permissions();
-             perms.set_mode(0o755);
-             fs::set_permissions(target_path, perms).map_err(|e| e.to_string())?;
-        } else {
-             return Err("Extracted binary not found".to_string());
-        }
-    }
+/// Stops the cloudflared download in progress; the partial file is kept for resuming.
+#[command]
+pub async fn cancel_cloudflared_download(state: State<'_, DownloadState>) -> Result<(), String> {
+    state.cancel();
+    Ok(())
+}
 
-    Ok(target_path.to_string_lossy().to_string())
+#[command]
+pub async fn list_cloudflared_versions<R: Runtime>(app: AppHandle<R>) -> Result<CloudflaredVersions, String> {
+    VersionStore::new(&app)
+        .and_then(|store| store.list())
+        .map_err(|e| e.to_string())
 }
 
 #[command]
-pub async fn get_latest_cloudflared_version() -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let response = client
-        .get("https://api.github.com/repos/cloudflare/cloudflared/releases/latest")
-        .header("User-Agent", "cloudbridge-desktop")
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+pub async fn use_cloudflared_version<R: Runtime>(app: AppHandle<R>, version: String) -> Result<(), String> {
+    let store = VersionStore::new(&app).map_err(|e| e.to_string())?;
+    let version = VersionStore::normalize(&version).map_err(|e| e.to_string())?;
+    store.activate(&version).map_err(|e| e.to_string())
+}
 
-    if !response.status().is_success() {
-        return Err(format!("GitHub API request failed: {}", response.status()));
+#[command]
+pub async fn rollback_cloudflared<R: Runtime>(app: AppHandle<R>) -> Result<String, String> {
+    VersionStore::new(&app)
+        .and_then(|store| store.rollback())
+        .map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn get_latest_cloudflared_version<R: Runtime>(app: AppHandle<R>) -> Result<String, String> {
+    latest_version(&app).await
+}
+
+pub(super) async fn latest_version<R: Runtime>(app: &AppHandle<R>) -> Result<String, String> {
+    if let Some(base_url) = mirror_url(app) {
+        return installer::latest_mirror_version(&base_url).await;
     }
 
-    let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
-    
+    let json = installer::fetch_release(None).await?;
+
     // tag_name usually looks like "2024.1.0" or "v2024.1.0"
     if let Some(tag_name) = json["tag_name"].as_str() {
         Ok(tag_name.trim_start_matches('v').to_string())
@@ -204,27 +163,42 @@ pub async fn get_latest_cloudflared_version() -> Result<String, String> {
     }
 }
 
-#[command]
-pub async fn check_cloudflared_version<R: Runtime>(app: AppHandle<R>) -> Result<String, String> {
-    let path = get_cloudflared_path(&app);
+/// Runs `cloudflared --version` on the binary in use, returning its path and output.
+pub(super) fn run_version<R: Runtime>(app: &AppHandle<R>) -> Result<(String, String), String> {
+    let path = get_cloudflared_path(app);
     let command = if path.exists() {
         path.to_string_lossy().to_string()
     } else {
         "cloudflared".to_string()
     };
 
-    let output = Command::new(command)
+    let output = Command::new(&command)
         .arg("--version")
         .output()
         .map_err(|e| e.to_string())?;
 
     if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        Ok((command, String::from_utf8_lossy(&output.stdout).to_string()))
     } else {
         Err(String::from_utf8_lossy(&output.stderr).to_string())
     }
 }
 
+#[command]
+pub async fn check_cloudflared_version<R: Runtime>(app: AppHandle<R>) -> Result<CloudflaredStatus, String> {
+    let (path, version_output) = run_version(&app)?;
+    let active_version = VersionStore::new(&app)
+        .ok()
+        .and_then(|store| store.active_version());
+
+    Ok(CloudflaredStatus {
+        active_version,
+        installed_version: CalVer::parse(&version_output).map(|v| v.to_string()),
+        path,
+        version_output,
+    })
+}
+
 #[command]
 pub async fn start_tcp_tunnel<R: Runtime>(
     app: AppHandle<R>,
@@ -289,3 +263,35 @@ pub async fn stop_tcp_tunnel(
         Err(format!("Tunnel {} not found", id))
     }
 }
+
+#[command]
+pub async fn write_ingress_config<R: Runtime>(app: AppHandle<R>, config: IngressConfig) -> Result<String, String> {
+    config
+        .write(&app)
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn validate_ingress_config<R: Runtime>(app: AppHandle<R>, config: IngressConfig) -> Result<String, String> {
+    ingress::validate(&app, &config).await.map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn start_ingress_tunnel<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IngressState>,
+    config: IngressConfig,
+) -> Result<u32, String> {
+    state.start(&app, &config).await.map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn stop_ingress_tunnel(state: State<'_, IngressState>, tunnel: String) -> Result<bool, String> {
+    state.stop(&tunnel).map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn list_ingress_tunnels(state: State<'_, IngressState>) -> Result<Vec<String>, String> {
+    Ok(state.running())
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum CloudflaredEvent {
+    UpdateAvailable { installed: String, latest: String },
+    Updated(String),
+    UpdateFailed(String),
+    DownloadProgress {
+        bytes: u64,
+        total: Option<u64>,
+        /// Average rate of the current attempt in bytes per second
+        rate: f64,
+    },
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tracing::{info, warn};
+use ts_rs::TS;
+
+use crate::remote::cloudflared_command;
+
+const INGRESS_DIR: &str = "ingress";
+const CATCH_ALL_SERVICE: &str = "http_status:404";
+
+/// `originRequest` options applied to a single ingress rule.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct OriginRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_tls_verify: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_host_header: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin_server_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_chunked_encoding: Option<bool>,
+}
+
+/// A local service published under a public hostname.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PublishedService {
+    pub hostname: String,
+    /// Local origin, e.g. `http://localhost:3000` or `tcp://localhost:5432`
+    pub service: String,
+    pub origin_request: Option<OriginRequest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct IngressConfig {
+    /// Name or UUID of a tunnel created with `cloudflared tunnel create`
+    pub tunnel: String,
+    /// Tunnel credentials JSON; cloudflared's default location is used when unset
+    pub credentials_file: Option<String>,
+    pub services: Vec<PublishedService>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ConfigFile<'a> {
+    tunnel: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    credentials_file: Option<&'a str>,
+    ingress: Vec<IngressRule<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IngressRule<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hostname: Option<&'a str>,
+    service: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin_request: Option<&'a OriginRequest>,
+}
+
+impl IngressConfig {
+    fn check(&self) -> anyhow::Result<()> {
+        if self.tunnel.is_empty()
+            || !self
+                .tunnel
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("invalid tunnel name: {:?}", self.tunnel);
+        }
+        if self.services.is_empty() {
+            bail!("at least one service is required");
+        }
+        for service in &self.services {
+            if service.hostname.trim().is_empty() || service.service.trim().is_empty() {
+                bail!("every service needs a hostname and a local url");
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders the cloudflared config file.
+    pub fn render(&self) -> anyhow::Result<String> {
+        self.check()?;
+
+        let mut ingress: Vec<IngressRule> = self
+            .services
+            .iter()
+            .map(|s| IngressRule {
+                hostname: Some(s.hostname.trim()),
+                service: s.service.trim(),
+                origin_request: s.origin_request.as_ref(),
+            })
+            .collect();
+        // cloudflared requires the last rule to match everything
+        ingress.push(IngressRule {
+            hostname: None,
+            service: CATCH_ALL_SERVICE,
+            origin_request: None,
+        });
+
+        Ok(serde_yaml::to_string(&ConfigFile {
+            tunnel: &self.tunnel,
+            credentials_file: self.credentials_file.as_deref(),
+            ingress,
+        })?)
+    }
+
+    /// Writes `<app data>/ingress/<tunnel>.yml` and returns its path.
+    pub fn write<R: Runtime>(&self, app: &AppHandle<R>) -> anyhow::Result<PathBuf> {
+        let rendered = self.render()?;
+        let dir = app.path().app_data_dir()?.join(INGRESS_DIR);
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!("{}.yml", self.tunnel));
+        fs::write(&path, rendered)?;
+        Ok(path)
+    }
+}
+
+/// Runs `cloudflared tunnel ingress validate` against the written config.
+pub async fn validate<R: Runtime>(app: &AppHandle<R>, config: &IngressConfig) -> anyhow::Result<String> {
+    let path = config.write(app)?;
+    let output = cloudflared_command(app)?
+        .arg("tunnel")
+        .arg("--config")
+        .arg(&path)
+        .args(["ingress", "validate"])
+        .output()
+        .await
+        .context("failed to run cloudflared")?;
+
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    if !output.status.success() {
+        bail!("ingress validation failed: {}", text.trim());
+    }
+    Ok(text.trim().to_string())
+}
+
+/// Published tunnels run by the app, keyed by tunnel name.
+pub struct IngressState {
+    processes: Mutex<HashMap<String, CommandChild>>,
+}
+
+impl IngressState {
+    pub fn new() -> Self {
+        Self {
+            processes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn start<R: Runtime>(&self, app: &AppHandle<R>, config: &IngressConfig) -> anyhow::Result<u32> {
+        validate(app, config).await?;
+        self.stop(&config.tunnel)?;
+
+        let path = config.write(app)?;
+        let (mut rx, child) = cloudflared_command(app)?
+            .arg("tunnel")
+            .arg("--config")
+            .arg(&path)
+            .args(["run", &config.tunnel])
+            .spawn()
+            .context("failed to start cloudflared")?;
+
+        let pid = child.pid();
+        self.processes
+            .lock()
+            .unwrap()
+            .insert(config.tunnel.clone(), child);
+
+        let app = app.clone();
+        let tunnel = config.tunnel.clone();
+        tauri::async_runtime::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Stderr(line) | CommandEvent::Stdout(line) => {
+                        info!(name: "Ingress", "{tunnel}: {}", String::from_utf8_lossy(&line).trim_end());
+                    }
+                    CommandEvent::Error(err) => warn!(name: "Ingress", "{tunnel}: {err}"),
+                    CommandEvent::Terminated(payload) => {
+                        warn!(name: "Ingress", "{tunnel} terminated: {payload:?}");
+                        // The tunnel may have been restarted in the meantime
+                        let state = app.state::<IngressState>();
+                        let mut processes = state.processes.lock().unwrap();
+                        if processes.get(&tunnel).is_some_and(|c| c.pid() == pid) {
+                            processes.remove(&tunnel);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        Ok(pid)
+    }
+
+    pub fn stop(&self, tunnel: &str) -> anyhow::Result<bool> {
+        match self.processes.lock().unwrap().remove(tunnel) {
+            Some(child) => {
+                child.kill()?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Kills every published tunnel; called when the app exits.
+    pub fn stop_all(&self) {
+        for (tunnel, child) in self.processes.lock().unwrap().drain() {
+            if let Err(e) = child.kill() {
+                warn!("cannot stop ingress tunnel {tunnel}: {e}");
+            }
+        }
+    }
+
+    /// Names of published tunnels whose process is still alive.
+    pub fn running(&self) -> Vec<String> {
+        self.processes.lock().unwrap().keys().cloned().collect()
+    }
+}
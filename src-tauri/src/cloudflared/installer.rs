@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use flate2::read::GzDecoder;
+use regex::Regex;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tar::EntryType;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::sync::Notify;
+use tracing::debug;
+use ts_rs::TS;
+
+use crate::util::{self, http_client, PanicMutex};
+
+use super::event::CloudflaredEvent;
+use super::versions::VersionStore;
+use super::CLOUDFLARED_EVENT;
+
+const RELEASES_API: &str = "https://api.github.com/repos/cloudflare/cloudflared/releases";
+const RELEASES_DOWNLOAD: &str = "https://github.com/cloudflare/cloudflared/releases/download";
+
+/// Mirrors are expected to follow this layout:
+///
+/// ```text
+/// <base>/index.json              {"latest": "2024.12.0", "versions": ["2024.11.1", "2024.12.0"]}
+/// <base>/<version>/<asset>       same file names as the GitHub release assets
+/// <base>/<version>/SHA256SUMS    `sha256sum` output or the GitHub release notes format
+/// ```
+const MIRROR_INDEX: &str = "index.json";
+const MIRROR_CHECKSUMS: &str = "SHA256SUMS";
+
+const DOWNLOADS_DIR: &str = "cloudflared-downloads";
+const PROGRESS_INTERVAL_MS: u64 = 250;
+
+const ARCHIVE_BINARY: &str = "cloudflared";
+const MAX_BINARY_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum InstallSource {
+    /// Official GitHub releases
+    GitHub,
+    /// Internal mirror configured in `Settings::cloudflared_mirror_url`
+    Mirror,
+    /// A binary or `.tgz` already on disk. Without `sha256` a `SHA256SUMS` file next to it is required.
+    LocalFile { path: String, sha256: Option<String> },
+}
+
+#[derive(Debug, Deserialize)]
+struct MirrorIndex {
+    latest: String,
+}
+
+/// A downloaded or local release asset together with the checksums it must match.
+pub struct Artifact {
+    pub version: String,
+    pub asset: String,
+    pub content: Vec<u8>,
+    pub checksums: HashMap<String, String>,
+}
+
+pub fn release_asset_name() -> Result<&'static str, String> {
+    let os = std::env::consts::OS;
+    let arch = std::env::consts::ARCH;
+
+    // macOS builds are only published as .tgz archives; Apple Silicon runs the amd64 build via Rosetta
+    match (os, arch) {
+        ("macos", "x86_64") | ("macos", "aarch64") => Ok("cloudflared-darwin-amd64.tgz"),
+        ("linux", "x86_64") => Ok("cloudflared-linux-amd64"),
+        ("linux", "x86") => Ok("cloudflared-linux-386"),
+        ("linux", "aarch64") => Ok("cloudflared-linux-arm64"),
+        ("linux", "arm") => Ok("cloudflared-linux-arm"),
+        ("windows", "x86_64") => Ok("cloudflared-windows-amd64.exe"),
+        ("windows", "x86") => Ok("cloudflared-windows-386.exe"),
+        _ => Err(format!("Unsupported platform: {} {}", os, arch)),
+    }
+}
+
+pub async fn fetch_release(tag: Option<&str>) -> Result<serde_json::Value, String> {
+    let url = match tag {
+        Some(tag) => format!("{}/tags/{}", RELEASES_API, tag),
+        None => format!("{}/latest", RELEASES_API),
+    };
+
+    let response = http_client()
+        .get(&url)
+        .header("User-Agent", "cloudbridge-desktop")
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!("GitHub API request failed: {}", response.status()));
+    }
+
+    response.json().await.map_err(|e| e.to_string())
+}
+
+async fn download(url: &str) -> Result<Vec<u8>, String> {
+    let response = http_client().get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to download {}: {}", url, response.status()));
+    }
+    let content = response.bytes().await.map_err(|e| e.to_string())?;
+    Ok(content.to_vec())
+}
+
+/// The asset download in progress, if any. Only one runs at a time, so
+/// `cancel_cloudflared_download` always means that one.
+#[derive(Default)]
+pub struct DownloadState {
+    current: Mutex<Option<Arc<Notify>>>,
+}
+
+impl DownloadState {
+    pub fn cancel(&self) {
+        if let Some(cancel) = self.current.lockp().as_ref() {
+            // Keeps a permit if the download is not waiting right now, so the cancel is not lost
+            cancel.notify_one();
+        }
+    }
+
+    fn begin(&self) -> Result<DownloadGuard<'_>, String> {
+        let mut current = self.current.lockp();
+        if current.is_some() {
+            return Err("Another cloudflared download is already in progress".to_string());
+        }
+        let cancel = Arc::new(Notify::new());
+        *current = Some(cancel.clone());
+        Ok(DownloadGuard { state: self, cancel })
+    }
+}
+
+/// Marks the download as finished when dropped, however it ends.
+struct DownloadGuard<'a> {
+    state: &'a DownloadState,
+    cancel: Arc<Notify>,
+}
+
+impl Drop for DownloadGuard<'_> {
+    fn drop(&mut self) {
+        *self.state.current.lockp() = None;
+    }
+}
+
+/// Streams a release asset into `<app data>/cloudflared-downloads/<version>-<asset>.part`,
+/// resuming from an existing partial file with an HTTP Range request and emitting progress events.
+/// A cancelled or failed download keeps the partial file so the next attempt can resume it.
+async fn download_asset<R: Runtime>(
+    app: &AppHandle<R>,
+    url: &str,
+    version: &str,
+    asset: &str,
+) -> Result<Vec<u8>, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join(DOWNLOADS_DIR);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let part_path = dir.join(format!("{}-{}.part", version, asset));
+
+    let state = app.state::<DownloadState>();
+    let download = state.begin()?;
+
+    let mut offset = fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+    let mut request = http_client().get(url);
+    if offset > 0 {
+        debug!("resuming {} from byte {}", asset, offset);
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+
+    let mut response = request.send().await.map_err(|e| e.to_string())?;
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT => {}
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+            // The partial file is already complete (or stale); verification decides which.
+            return finish_download(&part_path);
+        }
+        status if status.is_success() => offset = 0,
+        status => return Err(format!("Failed to download {}: {}", url, status)),
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(&part_path)
+        .map_err(|e| e.to_string())?;
+
+    let total = response.content_length().map(|len| len + offset);
+    let started = Instant::now();
+    let mut last_emit = Instant::now();
+    let mut received = 0u64;
+
+    loop {
+        let chunk = tokio::select! {
+            chunk = response.chunk() => chunk.map_err(|e| e.to_string())?,
+            _ = download.cancel.notified() => return Err("Download cancelled".to_string()),
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+        received += chunk.len() as u64;
+
+        if last_emit.elapsed() >= Duration::from_millis(PROGRESS_INTERVAL_MS) {
+            last_emit = Instant::now();
+            let _ = app.emit(
+                CLOUDFLARED_EVENT,
+                CloudflaredEvent::DownloadProgress {
+                    bytes: offset + received,
+                    total,
+                    rate: received as f64 / started.elapsed().as_secs_f64().max(0.001),
+                },
+            );
+        }
+    }
+
+    file.sync_all().map_err(|e| e.to_string())?;
+    drop(file);
+
+    let _ = app.emit(
+        CLOUDFLARED_EVENT,
+        CloudflaredEvent::DownloadProgress {
+            bytes: offset + received,
+            total,
+            rate: received as f64 / started.elapsed().as_secs_f64().max(0.001),
+        },
+    );
+
+    finish_download(&part_path)
+}
+
+fn finish_download(part_path: &Path) -> Result<Vec<u8>, String> {
+    let content = fs::read(part_path).map_err(|e| e.to_string())?;
+    let _ = fs::remove_file(part_path);
+    Ok(content)
+}
+
+pub async fn fetch_github<R: Runtime>(app: &AppHandle<R>, version: Option<&str>) -> Result<Artifact, String> {
+    let asset = release_asset_name()?;
+    let release = fetch_release(version).await?;
+    let tag = release["tag_name"]
+        .as_str()
+        .ok_or("Could not find tag_name in GitHub response")?;
+    let version = VersionStore::normalize(tag).map_err(|e| e.to_string())?;
+    let checksums = parse_checksums(release["body"].as_str().unwrap_or_default());
+    let url = format!("{}/{}/{}", RELEASES_DOWNLOAD, tag, asset);
+    let content = download_asset(app, &url, &version, asset).await?;
+
+    Ok(Artifact {
+        version,
+        asset: asset.to_string(),
+        content,
+        checksums,
+    })
+}
+
+pub async fn latest_mirror_version(base_url: &str) -> Result<String, String> {
+    let index = download(&format!("{}/{}", base_url.trim_end_matches('/'), MIRROR_INDEX)).await?;
+    let index: MirrorIndex = serde_json::from_slice(&index).map_err(|e| format!("Invalid mirror index: {}", e))?;
+    VersionStore::normalize(&index.latest).map_err(|e| e.to_string())
+}
+
+pub async fn fetch_mirror<R: Runtime>(
+    app: &AppHandle<R>,
+    base_url: &str,
+    version: Option<&str>,
+) -> Result<Artifact, String> {
+    let base_url = base_url.trim_end_matches('/');
+    let asset = release_asset_name()?;
+    let version = match version {
+        Some(version) => version.to_string(),
+        None => latest_mirror_version(base_url).await?,
+    };
+
+    let sums = download(&format!("{}/{}/{}", base_url, version, MIRROR_CHECKSUMS)).await?;
+    let checksums = parse_checksums(&String::from_utf8_lossy(&sums));
+    let url = format!("{}/{}/{}", base_url, version, asset);
+    let content = download_asset(app, &url, &version, asset).await?;
+
+    Ok(Artifact {
+        version,
+        asset: asset.to_string(),
+        content,
+        checksums,
+    })
+}
+
+pub fn read_local(path: &Path, version: Option<&str>, sha256: Option<&str>) -> Result<Artifact, String> {
+    let version = version.ok_or("A version is required when installing from a local file")?;
+    let asset = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("Invalid local file path")?
+        .to_string();
+
+    let checksums = match sha256 {
+        Some(sha256) => HashMap::from([(asset.clone(), sha256.trim().to_lowercase())]),
+        None => {
+            let sums_path = path.with_file_name(MIRROR_CHECKSUMS);
+            let sums = fs::read_to_string(&sums_path).map_err(|_| {
+                format!(
+                    "No checksum given and {} not found next to {}",
+                    MIRROR_CHECKSUMS, asset
+                )
+            })?;
+            parse_checksums(&sums)
+        }
+    };
+    let content = fs::read(path).map_err(|e| e.to_string())?;
+
+    Ok(Artifact {
+        version: version.to_string(),
+        asset,
+        content,
+        checksums,
+    })
+}
+
+/// Parses checksum listings in either the format cloudflared uses in its release notes
+/// (`cloudflared-linux-amd64: <hex digest>`) or `sha256sum` output (`<hex digest>  <file>`).
+pub fn parse_checksums(body: &str) -> HashMap<String, String> {
+    let notes = Regex::new(r"^\s*([\w.\-]+)\s*:\s*([0-9a-fA-F]{64})\s*$").expect("valid regex");
+    let sums = Regex::new(r"^\s*([0-9a-fA-F]{64})\s+\*?([\w.\-]+)\s*$").expect("valid regex");
+
+    body.lines()
+        .filter_map(|line| {
+            if let Some(caps) = notes.captures(line) {
+                Some((caps[1].to_string(), caps[2].to_lowercase()))
+            } else {
+                sums.captures(line)
+                    .map(|caps| (caps[2].to_string(), caps[1].to_lowercase()))
+            }
+        })
+        .collect()
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+pub fn verify_checksum(artifact: &Artifact) -> Result<(), String> {
+    let expected = artifact
+        .checksums
+        .get(&artifact.asset)
+        .ok_or_else(|| format!("No published SHA256 checksum for {}", artifact.asset))?;
+    let actual = sha256_hex(&artifact.content);
+
+    if &actual != expected {
+        return Err(format!(
+            "Checksum mismatch for {}: expected {}, got {}. The binary was not installed.",
+            artifact.asset, expected, actual
+        ));
+    }
+
+    Ok(())
+}
+
+/// Writes the binary atomically and marks it executable.
+fn write_atomically(target_path: &Path, content: &[u8]) -> Result<(), String> {
+    let result = (|| -> std::io::Result<()> {
+        util::write_atomically(target_path, content)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(target_path, fs::Permissions::from_mode(0o755))?;
+        }
+
+        Ok(())
+    })();
+
+    result.map_err(|e| e.to_string())
+}
+
+/// Verifies `artifact`, unpacks it if needed and stores it as an installed version.
+pub fn install(
+    store: &VersionStore,
+    artifact: Artifact,
+) -> Result<PathBuf, String> {
+    verify_checksum(&artifact)?;
+
+    let target_path = store.binary_path(&artifact.version);
+    if let Some(dir) = target_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    if artifact.asset.ends_with(".tgz") {
+        let binary = extract_tgz(&artifact.content)?;
+        write_atomically(&target_path, &binary)?;
+    } else {
+        write_atomically(&target_path, &artifact.content)?;
+    }
+
+    Ok(target_path)
+}
+
+/// Unpacks the cloudflared binary from a release `.tgz`. The archive must contain exactly one
+/// regular file named `cloudflared` (optionally under `./`) and nothing that could escape the target.
+pub fn extract_tgz(archive: &[u8]) -> Result<Vec<u8>, String> {
+    let mut tar = tar::Archive::new(GzDecoder::new(archive));
+    let mut binary: Option<Vec<u8>> = None;
+
+    for entry in tar.entries().map_err(|e| format!("Invalid cloudflared archive: {}", e))? {
+        let mut entry = entry.map_err(|e| format!("Invalid cloudflared archive: {}", e))?;
+        let path = entry
+            .path()
+            .map_err(|e| format!("Invalid path in cloudflared archive: {}", e))?
+            .into_owned();
+
+        let safe = path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !safe {
+            return Err(format!("Refusing unsafe path in cloudflared archive: {}", path.display()));
+        }
+
+        let name: PathBuf = path
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+
+        match entry.header().entry_type() {
+            EntryType::Directory if name.as_os_str().is_empty() => continue,
+            EntryType::Regular if name == Path::new(ARCHIVE_BINARY) => {
+                if binary.is_some() {
+                    return Err("cloudflared archive contains more than one cloudflared entry".to_string());
+                }
+                if entry.size() > MAX_BINARY_SIZE {
+                    return Err(format!("cloudflared entry is too large: {} bytes", entry.size()));
+                }
+
+                let mut content = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut content).map_err(|e| e.to_string())?;
+                binary = Some(content);
+            }
+            _ => {
+                return Err(format!("Unexpected entry in cloudflared archive: {}", path.display()));
+            }
+        }
+    }
+
+    binary.ok_or_else(|| "cloudflared archive does not contain a cloudflared binary".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_tgz;
+
+    /// Archives in `tests/fixtures/cloudflared`: each holds one `cloudflared` entry of the kind
+    /// its name says, next to an optional `./` directory.
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_bytes!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/cloudflared/",
+                $name
+            ))
+            .as_slice()
+        };
+    }
+
+    const BINARY: &[u8] = b"#!/bin/sh\necho 'cloudflared version 2024.12.0 (fixture)'\n";
+
+    #[test]
+    fn extracts_binary() {
+        assert_eq!(extract_tgz(fixture!("valid.tgz")).unwrap(), BINARY);
+    }
+
+    #[test]
+    fn rejects_parent_traversal() {
+        let err = extract_tgz(fixture!("traversal.tgz")).unwrap_err();
+        assert!(err.contains("unsafe path"), "{err}");
+    }
+
+    #[test]
+    fn rejects_absolute_path() {
+        let err = extract_tgz(fixture!("absolute.tgz")).unwrap_err();
+        assert!(err.contains("unsafe path"), "{err}");
+    }
+
+    #[test]
+    fn rejects_symlink() {
+        let err = extract_tgz(fixture!("symlink.tgz")).unwrap_err();
+        assert!(err.contains("Unexpected entry"), "{err}");
+    }
+
+    #[test]
+    fn rejects_hardlink() {
+        let err = extract_tgz(fixture!("hardlink.tgz")).unwrap_err();
+        assert!(err.contains("Unexpected entry"), "{err}");
+    }
+
+    #[test]
+    fn rejects_duplicate_binary() {
+        let err = extract_tgz(fixture!("duplicate.tgz")).unwrap_err();
+        assert!(err.contains("more than one"), "{err}");
+    }
+
+    #[test]
+    fn rejects_archive_without_binary() {
+        let err = extract_tgz(fixture!("missing-binary.tgz")).unwrap_err();
+        assert!(err.contains("does not contain"), "{err}");
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(extract_tgz(b"not a gzip stream").is_err());
+    }
+}
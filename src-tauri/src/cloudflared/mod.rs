@@ -1,8 +1,34 @@
 pub mod commands;
+pub mod event;
+mod ingress;
+mod installer;
+mod update;
+mod versions;
+
+use std::path::PathBuf;
 
 use tauri::{AppHandle, Manager, Runtime};
 
-pub fn setup<R: Runtime>(app: &AppHandle<R>) -> anyhow::Result<()> {
+pub const CLOUDFLARED_EVENT: &str = "cloudflared_event";
+
+pub fn setup(app: &AppHandle) -> anyhow::Result<()> {
     app.manage(commands::TunnelState::new());
+    app.manage(installer::DownloadState::default());
+    app.manage(ingress::IngressState::new());
+
+    tauri::async_runtime::spawn(update::watch(app.clone()));
+
     Ok(())
 }
+
+/// Stops the processes started by this module, so they do not outlive the app.
+pub fn shutdown<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(ingress) = app.try_state::<ingress::IngressState>() {
+        ingress.stop_all();
+    }
+}
+
+/// The cloudflared binary installed by the app, if any; callers fall back to the bundled sidecar.
+pub fn managed_binary<R: Runtime>(app: &AppHandle<R>) -> Option<PathBuf> {
+    Some(commands::get_cloudflared_path(app)).filter(|path| path.exists())
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{debug, info, warn};
+
+use crate::settings::SettingsState;
+use crate::util::PanicLock;
+
+use super::commands::{self, TunnelState};
+use super::event::CloudflaredEvent;
+use super::ingress::IngressState;
+use super::versions::CalVer;
+use super::CLOUDFLARED_EVENT;
+
+const UPDATE_CHECK_SECONDS: u64 = 6 * 60 * 60;
+
+/// Periodically compares the installed cloudflared with the latest release and,
+/// when enabled in settings, installs the update while no tunnel is running.
+pub async fn watch(app: AppHandle) {
+    let mut interval = tokio::time::interval(Duration::from_secs(UPDATE_CHECK_SECONDS));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = check(&app).await {
+            warn!("cloudflared update check failed: {e}");
+        }
+    }
+}
+
+async fn check(app: &AppHandle) -> anyhow::Result<()> {
+    let installed = match commands::run_version(app) {
+        Ok((_, output)) => CalVer::parse(&output),
+        Err(_) => None,
+    };
+    let Some(installed) = installed else {
+        debug!("cloudflared is not installed, skipping update check");
+        return Ok(());
+    };
+
+    let latest = commands::latest_version(app)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    let latest = CalVer::parse(&latest)
+        .ok_or_else(|| anyhow::anyhow!("cannot parse latest cloudflared version: {latest}"))?;
+
+    if latest <= installed {
+        debug!("cloudflared {installed} is up to date");
+        return Ok(());
+    }
+
+    info!("cloudflared update available: {installed} -> {latest}");
+    app.emit(
+        CLOUDFLARED_EVENT,
+        CloudflaredEvent::UpdateAvailable {
+            installed: installed.to_string(),
+            latest: latest.to_string(),
+        },
+    )?;
+
+    let auto_update = app
+        .state::<SettingsState>()
+        .readp()
+        .cloudflared_auto_update
+        .unwrap_or(false);
+    if !auto_update {
+        return Ok(());
+    }
+
+    if tunnels_active(app).await {
+        info!("postponing cloudflared auto-update while tunnels are active");
+        return Ok(());
+    }
+
+    match commands::install_release(app, Some(latest.to_string()), None).await {
+        Ok(_) => {
+            info!("cloudflared updated to {latest}");
+            app.emit(CLOUDFLARED_EVENT, CloudflaredEvent::Updated(latest.to_string()))?;
+        }
+        Err(e) => {
+            warn!("cloudflared auto-update failed: {e}");
+            app.emit(CLOUDFLARED_EVENT, CloudflaredEvent::UpdateFailed(e))?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn tunnels_active(app: &AppHandle) -> bool {
+    let tcp_tunnels = !app
+        .state::<TunnelState>()
+        .processes
+        .lock()
+        .unwrap()
+        .is_empty();
+
+    let published = !app.state::<IngressState>().running().is_empty();
+
+    tcp_tunnels || published || !crate::remote::connected_services(app).await.is_empty()
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+use ts_rs::TS;
+
+use crate::util;
+
+const VERSIONS_DIR: &str = "cloudflared-versions";
+const VERSIONS_FILE: &str = "versions.json";
+
+#[cfg(target_os = "windows")]
+const BINARY_NAME: &str = "cloudflared.exe";
+#[cfg(not(target_os = "windows"))]
+const BINARY_NAME: &str = "cloudflared";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct CloudflaredVersions {
+    pub installed: Vec<String>,
+    pub active: Option<String>,
+    pub previous: Option<String>,
+}
+
+/// cloudflared's calendar version (`YYYY.M.patch`), ordered chronologically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CalVer {
+    pub year: u32,
+    pub month: u32,
+    pub patch: u32,
+}
+
+impl CalVer {
+    /// Finds the first calendar version in `text`, so both tags like `v2024.12.0`
+    /// and `cloudflared version 2024.12.0 (built ...)` are accepted.
+    pub fn parse(text: &str) -> Option<Self> {
+        let re = Regex::new(r"(\d{4})\.(\d{1,2})\.(\d+)").expect("valid regex");
+        let caps = re.captures(text)?;
+
+        Some(Self {
+            year: caps[1].parse().ok()?,
+            month: caps[2].parse().ok()?,
+            patch: caps[3].parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for CalVer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.year, self.month, self.patch)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Selection {
+    active: Option<String>,
+    previous: Option<String>,
+}
+
+/// Versioned cloudflared binaries kept under `<app data>/cloudflared-versions/<version>/`.
+pub struct VersionStore {
+    root: PathBuf,
+}
+
+impl VersionStore {
+    pub fn new<R: Runtime>(app: &AppHandle<R>) -> anyhow::Result<Self> {
+        let root = app.path().app_data_dir()?.join(VERSIONS_DIR);
+        fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    /// Strips a leading `v` and rejects anything that is not a plain release tag.
+    pub fn normalize(version: &str) -> anyhow::Result<String> {
+        let version = version.trim().trim_start_matches('v');
+        if version.is_empty()
+            || !version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        {
+            bail!("invalid cloudflared version: {version:?}");
+        }
+        Ok(version.to_string())
+    }
+
+    pub fn binary_path(&self, version: &str) -> PathBuf {
+        self.root.join(version).join(BINARY_NAME)
+    }
+
+    pub fn active_version(&self) -> Option<String> {
+        self.read_selection().active
+    }
+
+    pub fn active_binary(&self) -> Option<PathBuf> {
+        self.active_version()
+            .map(|version| self.binary_path(&version))
+            .filter(|path| path.exists())
+    }
+
+    pub fn list(&self) -> anyhow::Result<CloudflaredVersions> {
+        let mut installed: Vec<String> = fs::read_dir(&self.root)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(BINARY_NAME).exists())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        // Release tags sort chronologically; anything else goes last
+        installed.sort_by(|a, b| match (CalVer::parse(a), CalVer::parse(b)) {
+            (Some(x), Some(y)) => x.cmp(&y).then_with(|| a.cmp(b)),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.cmp(b),
+        });
+
+        let selection = self.read_selection();
+        Ok(CloudflaredVersions {
+            installed,
+            active: selection.active,
+            previous: selection.previous,
+        })
+    }
+
+    /// Makes `version` active, remembering the current one for rollback.
+    pub fn activate(&self, version: &str) -> anyhow::Result<()> {
+        if !self.binary_path(version).exists() {
+            bail!("cloudflared {version} is not installed");
+        }
+
+        let mut selection = self.read_selection();
+        if selection.active.as_deref() != Some(version) {
+            selection.previous = selection.active.take();
+            selection.active = Some(version.to_string());
+            self.write_selection(&selection)?;
+        }
+
+        Ok(())
+    }
+
+    /// Swaps the active and previous versions, returning the version now active.
+    pub fn rollback(&self) -> anyhow::Result<String> {
+        let selection = self.read_selection();
+        let previous = selection
+            .previous
+            .ok_or_else(|| anyhow!("no previous cloudflared version to roll back to"))?;
+
+        self.activate(&previous)?;
+        Ok(previous)
+    }
+
+    fn read_selection(&self) -> Selection {
+        fs::read_to_string(self.root.join(VERSIONS_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn write_selection(&self, selection: &Selection) -> anyhow::Result<()> {
+        util::write_atomically(
+            &self.root.join(VERSIONS_FILE),
+            serde_json::to_string_pretty(selection)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+}
@@ -60,7 +60,7 @@ impl KdbxManager {
         let container_id = format!("{:x}", md5::compute(name.as_bytes()));
         
         // Создаем контейнер через Go бэкенд
-        let client = reqwest::Client::new();
+        let client = crate::util::http_client();
         let request_body = serde_json::json!({
             "name": name,
             "password": password,
@@ -2,6 +2,7 @@ use tauri::Manager;
 
 mod activity;
 mod keepass;
+mod migrations;
 mod remote;
 mod servers;
 mod settings;
@@ -57,6 +58,15 @@ use cloudflared::commands::{
     stop_tcp_tunnel,
     install_cloudflared,
     get_latest_cloudflared_version,
+    list_cloudflared_versions,
+    use_cloudflared_version,
+    rollback_cloudflared,
+    cancel_cloudflared_download,
+    write_ingress_config,
+    validate_ingress_config,
+    start_ingress_tunnel,
+    stop_ingress_tunnel,
+    list_ingress_tunnels,
 };
 
 use util::get_platform_info;
@@ -83,9 +93,11 @@ pub async fn run() {
         }))
         .plugin(tauri_plugin_opener::init())
         .setup(|app| {
-            app.manage(util::make_reqwest());
             app.manage(runtime_handle);
 
+            // До открытия хранилищ: старые файлы приводятся к текущей схеме
+            migrations::run(app.handle());
+
             activity::setup(app.handle())?;
             settings::setup(app.handle())?;
             keepass::setup(app.handle())?;
@@ -106,17 +118,43 @@ pub async fn run() {
             settings::patch_settings,
             servers::toggle_company_expansion,
             servers::load_service_credential,
+            servers::has_access_service_token,
+            servers::set_access_service_token,
+            servers::delete_access_service_token,
+            servers::add_company,
+            servers::update_company,
+            servers::delete_company,
             servers::add_server,
             servers::update_server,
             servers::delete_server,
+            servers::move_server,
             servers::add_service,
             servers::update_service,
+            servers::update_service_options,
             servers::delete_service,
+            servers::move_service,
+            servers::import_ssh_config,
+            servers::import_connections,
+            servers::commit_import,
+            servers::export_inventory,
+            servers::import_inventory,
+            servers::set_server_tags,
+            servers::set_service_tags,
+            servers::set_server_favorite,
+            servers::set_service_favorite,
+            servers::search_services,
+            servers::get_service_health,
+            servers::get_servers_since,
             servers::get_service,
             remote::connect_rdp_service_with_credentials,
             remote::connect_ssh_service_with_credentials,
             remote::connect_service,
             remote::disconnect_service,
+            remote::get_access_token_info,
+            remote::refresh_access_token,
+            remote::purge_access_token,
+            remote::get_tunnel_metrics,
+            remote::get_service_stats,
             // KeePass commands
             find_kdbx_files,
             load_containers,
@@ -156,18 +194,27 @@ pub async fn run() {
             stop_tcp_tunnel,
             install_cloudflared,
             get_latest_cloudflared_version,
+            list_cloudflared_versions,
+            use_cloudflared_version,
+            rollback_cloudflared,
+            cancel_cloudflared_download,
+            write_ingress_config,
+            validate_ingress_config,
+            start_ingress_tunnel,
+            stop_ingress_tunnel,
+            list_ingress_tunnels,
         ])
         .build(tauri::generate_context!())
         .expect("error while running tauri application");
 
-    app.run(move |_app_handle, event| {
-        if matches!(
-            event,
-            tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit
-        ) {
+    app.run(move |app_handle, event| match event {
+        tauri::RunEvent::ExitRequested { .. } => {
             /*
             TODO: handle gracefull exit etc.
             */
         }
+        // Published tunnels are detached from the UI and would otherwise keep running
+        tauri::RunEvent::Exit => cloudflared::shutdown(app_handle),
+        _ => {}
     });
 }
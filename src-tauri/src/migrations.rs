@@ -0,0 +1,440 @@
+//! Schema versions of the files the app persists.
+//!
+//! Every file is a JSON object with a top-level `schema_version`; files written before
+//! versioning have none and count as version 0. On startup, before any module opens its
+//! store, each file older than its schema is upgraded one version at a time. The original
+//! is kept next to it as `<file>.v<version>.bak`. Files that cannot be parsed or upgraded,
+//! including those from a newer release, are moved aside as `<file>.unreadable-<time>.bak`
+//! and the app starts with an empty store instead of failing or overwriting them.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+use tracing::{error, info, warn};
+
+use crate::util;
+
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Upgrades a payload from `from` to `from + 1`.
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    pub apply: fn(Value) -> anyhow::Result<Value>,
+}
+
+#[derive(Clone, Copy)]
+pub enum StoreDir {
+    /// `app_data_dir`, where `tauri-plugin-store` keeps its files
+    AppData,
+    /// The user config directory, see `settings`
+    Config,
+}
+
+pub struct Schema {
+    pub file: &'static str,
+    pub dir: StoreDir,
+    pub version: u32,
+    pub migrations: &'static [Migration],
+}
+
+pub const SERVERS: Schema = Schema {
+    file: "servers.json",
+    dir: StoreDir::AppData,
+    version: 1,
+    migrations: &[Migration {
+        from: 0,
+        description: "add schema version",
+        apply: Ok,
+    }],
+};
+
+pub const REMOTES: Schema = Schema {
+    file: "remotes.json",
+    dir: StoreDir::AppData,
+    version: 1,
+    migrations: &[Migration {
+        from: 0,
+        description: "add schema version",
+        apply: Ok,
+    }],
+};
+
+pub const SETTINGS: Schema = Schema {
+    file: "config.json",
+    dir: StoreDir::Config,
+    version: 1,
+    migrations: &[Migration {
+        from: 0,
+        description: "add schema version",
+        apply: Ok,
+    }],
+};
+
+pub const ACTIVITY: Schema = Schema {
+    file: crate::activity::state::LOG_FILE,
+    dir: StoreDir::AppData,
+    version: 1,
+    migrations: &[Migration {
+        from: 0,
+        description: "move the event list under \"events\"",
+        apply: activity_v1,
+    }],
+};
+
+const REGISTRY: &[&Schema] = &[&SERVERS, &REMOTES, &SETTINGS, &ACTIVITY];
+
+/// The log used to be a bare array of events.
+fn activity_v1(value: Value) -> anyhow::Result<Value> {
+    match value {
+        Value::Array(events) => Ok(json!({ "events": events })),
+        Value::Object(_) => Ok(value),
+        _ => anyhow::bail!("activity log is neither a list nor an object"),
+    }
+}
+
+fn set_version(value: &mut Value, version: u32) {
+    if let Value::Object(map) = value {
+        map.insert(SCHEMA_VERSION_KEY.to_string(), json!(version));
+    }
+}
+
+/// Marks `value` as written with the current version of `schema`.
+pub fn stamp(value: &mut Value, schema: &Schema) {
+    set_version(value, schema.version);
+}
+
+fn version_of(value: &Value) -> anyhow::Result<u32> {
+    match value.get(SCHEMA_VERSION_KEY) {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .context("invalid schema_version"),
+    }
+}
+
+/// Runs the migrations of `schema` on `value`. Returns the original version and the
+/// upgraded payload.
+pub fn upgrade(schema: &Schema, mut value: Value) -> anyhow::Result<(u32, Value)> {
+    let original = version_of(&value)?;
+    if original > schema.version {
+        anyhow::bail!(
+            "{} has schema version {original}, this release supports up to {}",
+            schema.file,
+            schema.version
+        );
+    }
+
+    let mut version = original;
+    while version < schema.version {
+        let migration = schema
+            .migrations
+            .iter()
+            .find(|m| m.from == version)
+            .with_context(|| format!("no migration for {} from version {version}", schema.file))?;
+        value = (migration.apply)(value).with_context(|| {
+            format!(
+                "migrating {} from version {version}: {}",
+                schema.file, migration.description
+            )
+        })?;
+        version += 1;
+        // Migrations may rebuild the object, so the version is written after every step
+        set_version(&mut value, version);
+    }
+
+    Ok((original, value))
+}
+
+fn path(app: &AppHandle, schema: &Schema) -> anyhow::Result<PathBuf> {
+    let dir = match schema.dir {
+        StoreDir::AppData => app.path().app_data_dir()?,
+        StoreDir::Config => app.path().config_dir()?,
+    };
+    Ok(dir.join(schema.file))
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// Missing, empty or already current
+    Current,
+    Upgraded {
+        from: u32,
+        backup: PathBuf,
+    },
+    /// Could not be parsed or upgraded and was moved to `backup`
+    SetAside {
+        backup: PathBuf,
+        reason: String,
+    },
+}
+
+/// Moves a file the app cannot read out of the way, keeping its content for the user.
+fn set_aside(path: &Path) -> anyhow::Result<PathBuf> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let stamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+    let backup = path.with_file_name(format!("{name}.unreadable-{stamp}.bak"));
+    fs::rename(path, &backup).with_context(|| format!("cannot move {} aside", path.display()))?;
+    Ok(backup)
+}
+
+fn migrate_file(schema: &Schema, path: &Path) -> anyhow::Result<Outcome> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Ok(Outcome::Current);
+    };
+    if text.trim().is_empty() {
+        return Ok(Outcome::Current);
+    }
+
+    let upgraded = serde_json::from_str(&text)
+        .with_context(|| format!("cannot parse {}", path.display()))
+        .and_then(|value| upgrade(schema, value));
+    let (original, value) = match upgraded {
+        Ok(upgraded) => upgraded,
+        Err(e) => {
+            return Ok(Outcome::SetAside {
+                backup: set_aside(path)?,
+                reason: format!("{e:#}"),
+            });
+        }
+    };
+    if original == schema.version {
+        return Ok(Outcome::Current);
+    }
+
+    let backup = path.with_file_name(format!("{}.v{original}.bak", schema.file));
+    fs::copy(path, &backup)
+        .with_context(|| format!("cannot back up {} before migrating", path.display()))?;
+    util::write_atomically(path, serde_json::to_string(&value)?.as_bytes())?;
+
+    Ok(Outcome::Upgraded {
+        from: original,
+        backup,
+    })
+}
+
+/// Upgrades every registered file; must run before the stores are opened.
+/// Failures are only logged, so a single bad file does not keep the app from starting.
+pub fn run(app: &AppHandle) {
+    for schema in REGISTRY {
+        match path(app, schema).and_then(|path| migrate_file(schema, &path)) {
+            Ok(Outcome::Current) => {}
+            Ok(Outcome::Upgraded { from, backup }) => info!(
+                "Migrated {} from schema version {from} to {}, backup at {}",
+                schema.file,
+                schema.version,
+                backup.display()
+            ),
+            Ok(Outcome::SetAside { backup, reason }) => error!(
+                "{reason}; moved {} to {}, starting with an empty store",
+                schema.file,
+                backup.display()
+            ),
+            Err(e) => warn!("Migration of {} failed: {e:#}", schema.file),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::PathBuf};
+
+    use serde_json::Value;
+    use uuid::Uuid;
+
+    use super::{
+        upgrade, Outcome, Schema, ACTIVITY, REMOTES, SCHEMA_VERSION_KEY, SERVERS, SETTINGS,
+    };
+    use crate::{
+        activity::event::ActivityEvent, servers::InnerCompanyServices, settings::Settings,
+    };
+
+    /// Files in `tests/fixtures/migrations` as releases before schema versioning wrote them.
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/migrations/",
+                $name
+            ))
+        };
+    }
+
+    const FIXTURES: &[(&Schema, &str)] = &[
+        (&SERVERS, fixture!("servers.v0.json")),
+        (&ACTIVITY, fixture!("activity.v0.json")),
+        (&SETTINGS, fixture!("config.v0.json")),
+        (&REMOTES, fixture!("remotes.v0.json")),
+    ];
+
+    /// Empty directory under the system temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("migrations-{}", Uuid::new_v4()));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn write(&self, schema: &Schema, content: &str) -> PathBuf {
+            let path = self.0.join(schema.file);
+            fs::write(&path, content).unwrap();
+            path
+        }
+
+        fn files(&self) -> Vec<String> {
+            let mut files: Vec<String> = fs::read_dir(&self.0)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            files.sort();
+            files
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn upgrade_fixture(schema: &Schema, text: &str) -> Value {
+        let (original, value) = upgrade(schema, serde_json::from_str(text).unwrap()).unwrap();
+        assert_eq!(original, 0);
+        assert_eq!(value[SCHEMA_VERSION_KEY], schema.version);
+        value
+    }
+
+    #[test]
+    fn upgrades_servers() {
+        let value = upgrade_fixture(&SERVERS, fixture!("servers.v0.json"));
+
+        let companies: InnerCompanyServices =
+            serde_json::from_value(value["servers_data"].clone()).unwrap();
+        assert_eq!(companies.len(), 1);
+        let services = &companies[0].servers[0].services;
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].host, "db-01.acme.internal");
+        assert_eq!(services[1].port, 3389);
+        assert_eq!(value["expanded_companies"][0], companies[0].id.to_string());
+    }
+
+    #[test]
+    fn upgrades_activity() {
+        let value = upgrade_fixture(&ACTIVITY, fixture!("activity.v0.json"));
+
+        let events: Vec<ActivityEvent> = serde_json::from_value(value["events"].clone()).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].id, "d4e5f6a7-b8c9-4d0e-8f1a-2b3c4d5e6f7a");
+    }
+
+    #[test]
+    fn upgrades_settings() {
+        let value = upgrade_fixture(&SETTINGS, fixture!("config.v0.json"));
+
+        let settings: Settings = serde_json::from_value(value).unwrap();
+        assert_eq!(settings.remember_me, Some(true));
+    }
+
+    #[test]
+    fn upgrades_remotes() {
+        let value = upgrade_fixture(&REMOTES, fixture!("remotes.v0.json"));
+
+        let ports: HashMap<Uuid, u16> =
+            serde_json::from_value(value["service_ports"].clone()).unwrap();
+        assert_eq!(ports.len(), 2);
+        assert!(ports.values().any(|&port| port == 53389));
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let value = serde_json::json!({ SCHEMA_VERSION_KEY: SERVERS.version + 1 });
+        let err = upgrade(&SERVERS, value).unwrap_err();
+        assert!(
+            err.to_string().contains("this release supports up to"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn migrates_files_and_keeps_backups() {
+        for (schema, text) in FIXTURES {
+            let dir = TempDir::new();
+            let path = dir.write(schema, text);
+
+            let backup = path.with_file_name(format!("{}.v0.bak", schema.file));
+            assert_eq!(
+                super::migrate_file(schema, &path).unwrap(),
+                Outcome::Upgraded {
+                    from: 0,
+                    backup: backup.clone()
+                }
+            );
+            assert_eq!(fs::read_to_string(&backup).unwrap(), *text);
+
+            let migrated: Value =
+                serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+            assert_eq!(
+                migrated[SCHEMA_VERSION_KEY], schema.version,
+                "{}",
+                schema.file
+            );
+            assert_eq!(dir.files().len(), 2, "{:?}", dir.files());
+        }
+    }
+
+    #[test]
+    fn leaves_current_files_alone() {
+        let dir = TempDir::new();
+        let current = format!(
+            r#"{{"service_ports":{{}},"{SCHEMA_VERSION_KEY}":{}}}"#,
+            REMOTES.version
+        );
+        let path = dir.write(&REMOTES, &current);
+
+        assert_eq!(
+            super::migrate_file(&REMOTES, &path).unwrap(),
+            Outcome::Current
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), current);
+        assert_eq!(dir.files(), [REMOTES.file]);
+    }
+
+    #[test]
+    fn skips_missing_files() {
+        let dir = TempDir::new();
+        let path = dir.0.join(SERVERS.file);
+
+        assert_eq!(
+            super::migrate_file(&SERVERS, &path).unwrap(),
+            Outcome::Current
+        );
+        assert!(dir.files().is_empty());
+    }
+
+    #[test]
+    fn sets_aside_corrupt_and_newer_files() {
+        let newer = format!(r#"{{"{SCHEMA_VERSION_KEY}":{}}}"#, SERVERS.version + 1);
+        for content in ["{\"servers_data\": [", newer.as_str()] {
+            let dir = TempDir::new();
+            let path = dir.write(&SERVERS, content);
+
+            let Outcome::SetAside { backup, .. } = super::migrate_file(&SERVERS, &path).unwrap()
+            else {
+                panic!("{content} was not set aside");
+            };
+            assert!(!path.exists());
+            assert_eq!(fs::read_to_string(&backup).unwrap(), content);
+            let name = backup.file_name().unwrap().to_string_lossy();
+            assert!(name.starts_with("servers.json.unreadable-"), "{name}");
+            assert_eq!(dir.files(), [name.into_owned()]);
+        }
+    }
+}
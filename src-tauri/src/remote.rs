@@ -1,15 +1,29 @@
+mod access_token;
 mod cloudflared;
 mod event;
+mod exposure;
 mod handle;
+mod hooks;
+mod idle;
+mod metrics;
+mod relay;
 mod state;
+mod stats;
 
+use std::{collections::HashMap, net::SocketAddr};
+
+pub use cloudflared::cloudflared_command;
+
+use access_token::AccessTokenInfo;
 use event::RemotesEvent;
+use metrics::{MetricsState, TunnelMetrics};
 use state::RemotesState;
+use stats::{ServiceStats, StatsState};
 use tauri::{AppHandle, Emitter, Manager};
 use tracing::debug;
 use uuid::Uuid;
 
-use crate::servers::ServersState;
+use crate::servers::{Credential, ServersState, ServiceOptions};
 use crate::util::invoke;
 
 const REMOTE_EVENT: &str = "remote_event";
@@ -24,7 +38,10 @@ pub async fn connect_service(app: AppHandle, service_id: Uuid) -> Result<(), Str
             .get_service(service_id)
             .await
             .ok_or(anyhow::anyhow!("service not found"))?;
-        let credentials = servers_state.load_service_credential(service_id).await?;
+        let credentials = servers_state
+            .load_service_credential(service_id)
+            .await?
+            .or_else(|| Credential::from_identity_file(&service));
 
         if let Some(credentials) = credentials {
             debug!("credentials found for service {service_id}");
@@ -149,8 +166,104 @@ pub async fn disconnect_service(app: AppHandle, service_id: Uuid) -> Result<(),
     invoke!(inner, app, service_id).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_access_token_info(
+    app: AppHandle,
+    service_id: Uuid,
+) -> Result<Option<AccessTokenInfo>, String> {
+    async fn inner(app: AppHandle, service_id: Uuid) -> anyhow::Result<Option<AccessTokenInfo>> {
+        let servers_state = app.state::<ServersState>();
+        let service = servers_state
+            .get_service(service_id)
+            .await
+            .ok_or(anyhow::anyhow!("service not found"))?;
+
+        access_token::inspect(&service)
+    }
+
+    invoke!(inner, app, service_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn refresh_access_token(app: AppHandle, service_id: Uuid) -> Result<(), String> {
+    async fn inner(app: AppHandle, service_id: Uuid) -> anyhow::Result<()> {
+        let servers_state = app.state::<ServersState>();
+        let service = servers_state
+            .get_service(service_id)
+            .await
+            .ok_or(anyhow::anyhow!("service not found"))?;
+
+        access_token::login(&app, &service)
+    }
+
+    invoke!(inner, app, service_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn purge_access_token(app: AppHandle, service_id: Uuid) -> Result<usize, String> {
+    async fn inner(app: AppHandle, service_id: Uuid) -> anyhow::Result<usize> {
+        let servers_state = app.state::<ServersState>();
+        let service = servers_state
+            .get_service(service_id)
+            .await
+            .ok_or(anyhow::anyhow!("service not found"))?;
+
+        let removed = access_token::purge(&service)?;
+        debug!("purged {removed} access tokens for service {service_id}");
+        Ok(removed)
+    }
+
+    invoke!(inner, app, service_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_tunnel_metrics(
+    app: AppHandle,
+    service_id: Option<Uuid>,
+) -> Result<Vec<TunnelMetrics>, String> {
+    Ok(app.state::<MetricsState>().get(service_id).await)
+}
+
+#[tauri::command]
+pub async fn get_service_stats(
+    app: AppHandle,
+    service_id: Option<Uuid>,
+) -> Result<Vec<ServiceStats>, String> {
+    Ok(app.state::<StatsState>().get(service_id).await)
+}
+
+/// Rejects a bind address or allowlist entry the relay could not use.
+pub fn check_exposure(options: &ServiceOptions) -> anyhow::Result<()> {
+    exposure::bind_ip(options)?;
+    exposure::SourceAllowlist::parse(&options.allowed_sources)?;
+    Ok(())
+}
+
+/// Services that currently have a running tunnel.
+pub async fn connected_services(app: &AppHandle) -> Vec<Uuid> {
+    match app.try_state::<RemotesState>() {
+        Some(state) => state.connected_services().await,
+        None => Vec::new(),
+    }
+}
+
+/// cloudflared endpoints of connected services, which the reachability probe goes through.
+pub async fn tunnel_endpoints(app: &AppHandle) -> HashMap<Uuid, SocketAddr> {
+    match app.try_state::<RemotesState>() {
+        Some(state) => state.tunnel_endpoints().await.into_iter().collect(),
+        None => HashMap::new(),
+    }
+}
+
 pub fn setup(app: &AppHandle) -> anyhow::Result<()> {
     app.manage(RemotesState::new(app)?);
+    app.manage(MetricsState::default());
+    app.manage(StatsState::default());
+
+    tauri::async_runtime::spawn(idle::watch(app.clone()));
+    tauri::async_runtime::spawn(access_token::watch(app.clone()));
+    tauri::async_runtime::spawn(metrics::watch(app.clone()));
+    tauri::async_runtime::spawn(stats::watch(app.clone()));
 
     Ok(())
 }
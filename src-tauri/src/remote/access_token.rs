@@ -0,0 +1,194 @@
+use std::{collections::HashSet, fs, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::servers::{AccessMode, AccessServiceToken, Service, ServersState};
+
+use super::{cloudflared::cloudflared_command, event::RemotesEvent, state::RemotesState, REMOTE_EVENT};
+
+const CHECK_SECONDS: u64 = 60;
+const REFRESH_BEFORE_SECS: i64 = 5 * 60;
+
+/// What cloudflared's cached Access JWT for a service says about the session.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct AccessTokenInfo {
+    pub hostname: String,
+    pub path: String,
+    pub expires_at: Option<String>,
+    pub remaining_secs: Option<i64>,
+    pub email: Option<String>,
+    pub subject: Option<String>,
+    pub issuer: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    exp: Option<i64>,
+    email: Option<String>,
+    sub: Option<String>,
+    iss: Option<String>,
+}
+
+pub fn access_hostname(service: &Service) -> Option<&str> {
+    if service.options.access_mode != AccessMode::Access {
+        return None;
+    }
+    service
+        .options
+        .access_hostname
+        .as_deref()
+        .map(str::trim)
+        .filter(|h| !h.is_empty())
+}
+
+/// cloudflared stores tokens as `~/.cloudflared/<hostname>-<aud>-token` (older releases omit the aud).
+fn token_files(hostname: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = dirs::home_dir()
+        .ok_or(anyhow::anyhow!("cannot determine home directory"))?
+        .join(".cloudflared");
+    let prefix = format!("{hostname}-");
+
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with("-token"))
+        })
+        .collect();
+
+    files.sort_by_key(|path| {
+        fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+    });
+    files.reverse();
+
+    Ok(files)
+}
+
+fn decode(hostname: &str, path: PathBuf) -> anyhow::Result<AccessTokenInfo> {
+    let token = fs::read_to_string(&path)?;
+    let payload = token
+        .trim()
+        .split('.')
+        .nth(1)
+        .ok_or(anyhow::anyhow!("malformed Access token"))?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .context("malformed Access token payload")?;
+    let claims: Claims = serde_json::from_slice(&payload).context("malformed Access token claims")?;
+
+    let expires_at = claims
+        .exp
+        .and_then(|exp| DateTime::<Utc>::from_timestamp(exp, 0));
+
+    Ok(AccessTokenInfo {
+        hostname: hostname.to_string(),
+        path: path.to_string_lossy().to_string(),
+        expires_at: expires_at.map(|at| at.to_rfc3339()),
+        remaining_secs: expires_at.map(|at| (at - Utc::now()).num_seconds()),
+        email: claims.email,
+        subject: claims.sub,
+        issuer: claims.iss,
+    })
+}
+
+/// Reads the newest cached token for the service's Access hostname.
+pub fn inspect(service: &Service) -> anyhow::Result<Option<AccessTokenInfo>> {
+    let Some(hostname) = access_hostname(service) else {
+        return Ok(None);
+    };
+
+    match token_files(hostname)?.into_iter().next() {
+        Some(path) => decode(hostname, path).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Removes every cached token for the service's Access hostname.
+pub fn purge(service: &Service) -> anyhow::Result<usize> {
+    let hostname = access_hostname(service)
+        .ok_or(anyhow::anyhow!("service has no Access hostname"))?;
+
+    let files = token_files(hostname)?;
+    for path in &files {
+        fs::remove_file(path)?;
+    }
+
+    Ok(files.len())
+}
+
+/// Starts `cloudflared access login`, which opens the browser to renew the token.
+pub fn login(app: &AppHandle, service: &Service) -> anyhow::Result<()> {
+    let hostname = access_hostname(service)
+        .ok_or(anyhow::anyhow!("service has no Access hostname"))?;
+
+    info!("starting cloudflared access login for {hostname}");
+    cloudflared_command(app)?
+        .args(["access", "login", &format!("https://{hostname}")])
+        .spawn()?;
+
+    Ok(())
+}
+
+/// Renews Access tokens of connected services shortly before they expire,
+/// so the browser prompt happens at a predictable moment rather than mid-session.
+pub async fn watch(app: AppHandle) {
+    let mut refreshed: HashSet<(Uuid, String)> = HashSet::new();
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_SECONDS));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = check(&app, &mut refreshed).await {
+            warn!("access token check failed: {e}");
+        }
+    }
+}
+
+async fn check(app: &AppHandle, refreshed: &mut HashSet<(Uuid, String)>) -> anyhow::Result<()> {
+    let remotes_state = app.state::<RemotesState>();
+    let servers_state = app.state::<ServersState>();
+
+    for service_id in remotes_state.connected_services().await {
+        let Some(service) = servers_state.get_service(service_id).await else {
+            continue;
+        };
+        if access_hostname(&service).is_none() || AccessServiceToken::load(service_id)?.is_some() {
+            continue;
+        }
+
+        let Some(info) = inspect(&service)? else {
+            continue;
+        };
+        let (Some(remaining), Some(expires_at)) = (info.remaining_secs, info.expires_at) else {
+            continue;
+        };
+
+        if remaining <= REFRESH_BEFORE_SECS && refreshed.insert((service_id, expires_at)) {
+            app.emit(
+                REMOTE_EVENT,
+                RemotesEvent::AccessTokenExpiring {
+                    service: service_id,
+                    remaining_secs: remaining,
+                },
+            )?;
+            login(app, &service)?;
+        }
+    }
+
+    Ok(())
+}
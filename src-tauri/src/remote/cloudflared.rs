@@ -1,44 +1,134 @@
-use tauri::AppHandle;
+use tauri::{AppHandle, Runtime};
 use tauri_plugin_shell::{
-    process::{CommandChild, CommandEvent},
+    process::{Command, CommandChild, CommandEvent},
     ShellExt,
 };
 use tokio::sync::mpsc::Receiver;
 
-use crate::servers::Service;
+use crate::servers::{AccessMode, AccessServiceToken, Service};
+
+use super::{
+    exposure::{self, SourceAllowlist},
+    relay::Relay,
+};
+
+/// The managed cloudflared binary if installed, otherwise the bundled sidecar.
+pub fn cloudflared_command<R: Runtime>(app: &AppHandle<R>) -> anyhow::Result<Command> {
+    Ok(match crate::cloudflared::managed_binary(app) {
+        Some(path) => app.shell().command(path),
+        None => app.shell().sidecar("cloudflared")?,
+    })
+}
 
 pub struct Access {
     pub url: String,
+    /// Address the relay listens on
+    pub listen: std::net::SocketAddr,
+    pub relay: Relay,
+    /// Loopback port cloudflared itself listens on, behind the relay
+    pub tunnel_port: u16,
+    /// Loopback port of cloudflared's Prometheus endpoint, if this mode exposes one
+    pub metrics_port: Option<u16>,
     cmd: CommandChild,
 }
 
 impl Access {
-    pub fn new(
+    /// Starts cloudflared on `tunnel_port` and exposes it on `bind_port` through a relay,
+    /// listening on the service's bind address (loopback by default). `metrics_port` is only
+    /// used in tunnel mode, `cloudflared access tcp` has no metrics endpoint.
+    pub async fn new(
         app: &AppHandle,
         service: &Service,
         bind_port: u16,
+        tunnel_port: u16,
+        metrics_port: Option<u16>,
     ) -> anyhow::Result<(Receiver<CommandEvent>, Self)> {
-        let target = format!("{}:{}", service.host, service.port);
-        let url = format!("localhost:{}", bind_port);
-        
-        // Используем cloudflared tunnel для прямого подключения
-        // cloudflared tunnel --url tcp://host:port --local-port local_port
-        let (rx, cmd) = app
-            .shell()
-            .sidecar("cloudflared")?
-            .args([
-                "tunnel",
-                "--url",
-                &format!("tcp://{}", target),
-                "--local-port",
-                &bind_port.to_string(),
-            ])
-            .spawn()?;
-
-        Ok((rx, Self { url, cmd }))
+        let listen: std::net::SocketAddr =
+            (exposure::bind_ip(&service.options)?, bind_port).into();
+        let allowlist =
+            SourceAllowlist::parse(&service.options.allowed_sources)?.listening_on(listen.ip());
+        let client = exposure::client_addr(listen);
+        let url = if client.ip() == std::net::Ipv4Addr::LOCALHOST {
+            format!("localhost:{}", bind_port)
+        } else {
+            client.to_string()
+        };
+        let tunnel_url = format!("localhost:{}", tunnel_port);
+
+        let args: Vec<String> = match service.options.access_mode {
+            // cloudflared access tcp --hostname <hostname> --url localhost:<port>
+            AccessMode::Access => {
+                let hostname = service
+                    .options
+                    .access_hostname
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|h| !h.is_empty())
+                    .ok_or(anyhow::anyhow!("Access hostname is not set for this service"))?;
+                vec![
+                    "access".into(),
+                    "tcp".into(),
+                    "--hostname".into(),
+                    hostname.into(),
+                    "--url".into(),
+                    tunnel_url,
+                ]
+            }
+            // cloudflared tunnel --metrics 127.0.0.1:<port> --url tcp://host:port --local-port local_port
+            AccessMode::Tunnel => {
+                let mut args: Vec<String> = vec!["tunnel".into()];
+                if let Some(metrics_port) = metrics_port {
+                    args.extend(["--metrics".into(), format!("127.0.0.1:{}", metrics_port)]);
+                }
+                args.extend([
+                    "--url".into(),
+                    format!("tcp://{}:{}", service.host, service.port),
+                    "--local-port".into(),
+                    tunnel_port.to_string(),
+                ]);
+                args
+            }
+        };
+        let metrics_port = match service.options.access_mode {
+            AccessMode::Access => None,
+            AccessMode::Tunnel => metrics_port,
+        };
+
+        let command = cloudflared_command(app)?;
+        let command = match service.options.access_mode {
+            AccessMode::Access => match AccessServiceToken::load(service.id)? {
+                Some(token) => command.envs(token.envs()),
+                None => command,
+            },
+            AccessMode::Tunnel => command,
+        };
+        let (rx, cmd) = command.args(args).spawn()?;
+
+        let relay = match Relay::bind(listen, ([127, 0, 0, 1], tunnel_port).into(), allowlist)
+            .await
+        {
+            Ok(relay) => relay,
+            Err(e) => {
+                cmd.kill()?;
+                return Err(e);
+            }
+        };
+
+        Ok((
+            rx,
+            Self {
+                url,
+                listen,
+                relay,
+                tunnel_port,
+                metrics_port,
+                cmd,
+            },
+        ))
     }
 
     pub fn stop(self) -> anyhow::Result<()> {
+        self.relay.stop();
         self.cmd.kill()?;
         Ok(())
     }
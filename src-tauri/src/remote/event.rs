@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use uuid::Uuid;
 
+use super::{metrics::TunnelMetrics, stats::ServiceStats};
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub enum RemotesEvent {
@@ -9,4 +11,14 @@ pub enum RemotesEvent {
     Disconnected(Uuid),
     PromptCredentials(Uuid),
     ConnectedServices(Vec<Uuid>),
+    IdleWarning {
+        service: Uuid,
+        remaining_secs: u64,
+    },
+    AccessTokenExpiring {
+        service: Uuid,
+        remaining_secs: i64,
+    },
+    Metrics(Vec<TunnelMetrics>),
+    Stats(Vec<ServiceStats>),
 }
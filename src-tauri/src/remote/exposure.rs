@@ -0,0 +1,154 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use anyhow::Context;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::activity::{
+    event::{ActivityEventType, ActivitySeverity},
+    ActivityState,
+};
+use crate::servers::{Service, ServiceOptions};
+
+/// Source addresses allowed to use a relay. Connections from this machine are always
+/// accepted: loopback, the address the relay listens on and the host's interface addresses.
+#[derive(Debug, Clone, Default)]
+pub struct SourceAllowlist {
+    networks: Vec<(IpAddr, u8)>,
+    listen: Option<IpAddr>,
+}
+
+impl SourceAllowlist {
+    /// Accepts plain addresses (`192.168.1.20`, `fd00::5`) and CIDR ranges (`172.17.0.0/16`).
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> anyhow::Result<Self> {
+        let mut networks = Vec::new();
+
+        for entry in entries.iter().map(|e| e.as_ref().trim()).filter(|e| !e.is_empty()) {
+            let (addr, prefix) = match entry.split_once('/') {
+                Some((addr, prefix)) => (addr, Some(prefix)),
+                None => (entry, None),
+            };
+            let addr: IpAddr = addr
+                .parse()
+                .with_context(|| format!("invalid allowlist address: {entry}"))?;
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            let prefix = match prefix {
+                Some(p) => p
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|p| *p <= max)
+                    .with_context(|| format!("invalid allowlist prefix: {entry}"))?,
+                None => max,
+            };
+            networks.push((addr, prefix));
+        }
+
+        Ok(Self {
+            networks,
+            listen: None,
+        })
+    }
+
+    /// Also accepts `listen`, the source address local clients get when they dial it.
+    pub fn listening_on(mut self, listen: IpAddr) -> Self {
+        let listen = canonical(listen);
+        self.listen = Some(listen).filter(|ip| !ip.is_unspecified());
+        self
+    }
+
+    pub fn allows(&self, peer: IpAddr) -> bool {
+        let peer = canonical(peer);
+        if peer.is_loopback() || self.listen == Some(peer) || is_own_address(peer) {
+            return true;
+        }
+
+        self.networks.iter().any(|(net, prefix)| match (canonical(*net), peer) {
+            (IpAddr::V4(net), IpAddr::V4(peer)) => {
+                let mask = u32::MAX.checked_shl(32 - *prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(peer) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(peer)) => {
+                let mask = u128::MAX.checked_shl(128 - *prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(peer) & mask
+            }
+            _ => false,
+        })
+    }
+}
+
+/// Interfaces are read on every check, since addresses change with DHCP or VPN reconnects.
+fn is_own_address(peer: IpAddr) -> bool {
+    match if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces.iter().any(|i| canonical(i.ip()) == peer),
+        Err(e) => {
+            warn!("cannot list network interfaces: {e}");
+            false
+        }
+    }
+}
+
+/// IPv4 peers on a dual-stack socket show up as `::ffff:a.b.c.d`.
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr),
+        v4 => v4,
+    }
+}
+
+/// Address the service's local endpoint listens on; loopback unless LAN sharing is configured.
+pub fn bind_ip(options: &ServiceOptions) -> anyhow::Result<IpAddr> {
+    match options
+        .bind_address
+        .as_deref()
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+    {
+        Some(addr) => addr
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .with_context(|| format!("invalid bind address: {addr}")),
+        None => Ok(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+    }
+}
+
+/// Address local clients should dial: the wildcard addresses are only reachable via loopback.
+pub fn client_addr(bind: SocketAddr) -> SocketAddr {
+    match bind.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => (Ipv4Addr::LOCALHOST, bind.port()).into(),
+        IpAddr::V6(ip) if ip.is_unspecified() => (Ipv6Addr::LOCALHOST, bind.port()).into(),
+        _ => bind,
+    }
+}
+
+/// Records a warning in the activity log when the endpoint is reachable beyond this machine.
+pub async fn report(app: &AppHandle, service: &Service, listen: SocketAddr) {
+    if listen.ip().is_loopback() {
+        return;
+    }
+    warn!(
+        "service {} is exposed on {listen}, allowed sources: {:?}",
+        service.id, service.options.allowed_sources
+    );
+
+    if let Some(activity_state) = app.try_state::<ActivityState>() {
+        let _ = activity_state
+            .add_event(
+                ActivityEventType::ServiceExposedOnNetwork,
+                format!(
+                    "ВНИМАНИЕ: сервис {}:{} доступен в сети по адресу {}",
+                    service.host, service.port, listen
+                ),
+                Some(serde_json::json!({
+                    "service_id": service.id,
+                    "listen": listen.to_string(),
+                    "allowed_sources": service.options.allowed_sources
+                })),
+                Some(service.id),
+                None,
+                None,
+                ActivitySeverity::Warning,
+            )
+            .await;
+    }
+}
@@ -25,6 +25,7 @@ impl RemoteHandle {
         protocol: Protocol,
         url: &str,
         credential: &Credential,
+        identity_file: Option<&str>,
     ) -> anyhow::Result<(Receiver<CommandEvent>, Self)> {
         let shell = app.shell();
         match (protocol, credential) {
@@ -72,6 +73,18 @@ impl RemoteHandle {
 
                 Ok((rx, Self::Ssh(cmd)))
             }
+            // No password: authenticate with the key file configured for the service
+            (Protocol::Ssh, Credential::SshUserPassword { login, password })
+                if password.is_empty() && identity_file.is_some() =>
+            {
+                let identity_file = identity_file.unwrap_or_default();
+                let (rx, cmd) = shell
+                    .command("ssh")
+                    .args(["-i", identity_file, &format!("{}@{}", login, url)])
+                    .spawn()?;
+
+                Ok((rx, Self::Ssh(cmd)))
+            }
             (Protocol::Ssh, Credential::SshUserPassword { login, password }) => {
                 let (rx, cmd) = shell
                     .command("sshpass")
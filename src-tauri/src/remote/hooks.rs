@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::servers::{ConnectionHooks, ServersState};
+use crate::settings::AppHandleSettigs;
+use crate::util::PanicLock;
+
+const HOOK_TIMEOUT_SECONDS: u64 = 60;
+
+#[derive(Debug, Clone, Copy)]
+pub enum HookPoint {
+    PreConnect,
+    PostConnect,
+    PostDisconnect,
+}
+
+impl HookPoint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookPoint::PreConnect => "pre_connect",
+            HookPoint::PostConnect => "post_connect",
+            HookPoint::PostDisconnect => "post_disconnect",
+        }
+    }
+
+    fn select(self, hooks: &ConnectionHooks) -> Option<&str> {
+        match self {
+            HookPoint::PreConnect => hooks.pre_connect.as_deref(),
+            HookPoint::PostConnect => hooks.post_connect.as_deref(),
+            HookPoint::PostDisconnect => hooks.post_disconnect.as_deref(),
+        }
+        .filter(|cmd| !cmd.trim().is_empty())
+    }
+}
+
+/// Runs the global hook and then the service hook for `point`, stopping at the first failure.
+pub async fn run(
+    app: &AppHandle,
+    point: HookPoint,
+    service_id: Uuid,
+    local_port: Option<u16>,
+) -> anyhow::Result<()> {
+    let Some(service) = app.state::<ServersState>().get_service(service_id).await else {
+        return Ok(());
+    };
+    let global = app.settings().readp().hooks.clone().unwrap_or_default();
+
+    let commands = [point.select(&global), point.select(&service.options.hooks)];
+    for command in commands.into_iter().flatten() {
+        debug!("running {} hook for service {service_id}", point.as_str());
+
+        let envs = [
+            ("ARGO_HOOK", point.as_str().to_string()),
+            ("ARGO_SERVICE_ID", service_id.to_string()),
+            ("ARGO_SERVICE_HOST", service.host.clone()),
+            ("ARGO_SERVICE_PORT", service.port.to_string()),
+            ("ARGO_SERVICE_PROTOCOL", service.protocol.as_str().to_string()),
+            (
+                "ARGO_LOCAL_PORT",
+                local_port.map(|p| p.to_string()).unwrap_or_default(),
+            ),
+        ];
+
+        let shell = if cfg!(target_os = "windows") {
+            app.shell().command("cmd").args(["/C", command])
+        } else {
+            app.shell().command("sh").args(["-c", command])
+        };
+
+        let (mut rx, child) = shell.envs(envs).spawn()?;
+        let output = async move {
+            let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+            while let Some(event) = rx.recv().await {
+                match event {
+                    // События приходят построчно, без перевода строки
+                    CommandEvent::Stdout(line) => stdout.extend(line.into_iter().chain([b'\n'])),
+                    CommandEvent::Stderr(line) => stderr.extend(line.into_iter().chain([b'\n'])),
+                    CommandEvent::Error(e) => anyhow::bail!("{e}"),
+                    CommandEvent::Terminated(payload) => return Ok((payload.code, stdout, stderr)),
+                    _ => {}
+                }
+            }
+            anyhow::bail!("hook process ended without an exit status")
+        };
+
+        let (code, stdout, stderr) =
+            match tokio::time::timeout(Duration::from_secs(HOOK_TIMEOUT_SECONDS), output).await {
+                Ok(result) => result?,
+                Err(_) => {
+                    // Без kill процесс хука продолжил бы работать после таймаута
+                    if let Err(e) = child.kill() {
+                        warn!("cannot kill timed out {} hook: {e}", point.as_str());
+                    }
+                    anyhow::bail!("{} hook timed out", point.as_str());
+                }
+            };
+
+        if code != Some(0) {
+            anyhow::bail!(
+                "{} hook failed ({:?}): {}",
+                point.as_str(),
+                code,
+                String::from_utf8_lossy(&stderr).trim()
+            );
+        }
+
+        info!(
+            "{} hook for service {service_id} finished: {}",
+            point.as_str(),
+            String::from_utf8_lossy(&stdout).trim()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::{collections::HashSet, time::Duration};
+
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::activity::{
+    event::{ActivityEventType, ActivitySeverity},
+    ActivityState,
+};
+use crate::servers::ServersState;
+use crate::settings::AppHandleSettigs;
+use crate::util::PanicLock;
+
+use super::{event::RemotesEvent, state::RemotesState, REMOTE_EVENT};
+
+const CHECK_SECONDS: u64 = 5;
+const DEFAULT_WARNING_SECS: u64 = 60;
+
+/// Periodically closes tunnels that carried no traffic for longer than their idle timeout.
+pub async fn watch(app: AppHandle) {
+    let mut warned: HashSet<Uuid> = HashSet::new();
+    let mut interval = tokio::time::interval(Duration::from_secs(CHECK_SECONDS));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = check(&app, &mut warned).await {
+            warn!("idle check failed: {e}");
+        }
+    }
+}
+
+async fn check(app: &AppHandle, warned: &mut HashSet<Uuid>) -> anyhow::Result<()> {
+    let remotes_state = app.state::<RemotesState>();
+    let servers_state = app.state::<ServersState>();
+    let (global_timeout, warning_secs) = {
+        let settings = app.settings();
+        let settings = settings.readp();
+        (
+            settings.idle_timeout_secs,
+            settings.idle_warning_secs.unwrap_or(DEFAULT_WARNING_SECS),
+        )
+    };
+
+    let idle = remotes_state.idle_durations().await;
+    warned.retain(|id| idle.iter().any(|(connected, _)| connected == id));
+
+    for (service_id, idle_for) in idle {
+        let Some(service) = servers_state.get_service(service_id).await else {
+            continue;
+        };
+        let timeout = match service.options.idle_timeout_secs.or(global_timeout) {
+            Some(timeout) if timeout > 0 => timeout,
+            _ => continue,
+        };
+        let idle_secs = idle_for.as_secs();
+
+        if idle_secs >= timeout {
+            info!("service {service_id} idle for {idle_secs}s, disconnecting");
+            warned.remove(&service_id);
+            remotes_state.disconnect_service(&service_id).await?;
+            app.emit(REMOTE_EVENT, RemotesEvent::Disconnected(service_id))?;
+
+            if let Some(activity_state) = app.try_state::<ActivityState>() {
+                let _ = activity_state
+                    .add_event(
+                        ActivityEventType::ServiceIdleDisconnected,
+                        format!(
+                            "Сервис {}:{} отключен после {} с бездействия",
+                            service.host, service.port, idle_secs
+                        ),
+                        Some(serde_json::json!({
+                            "service_id": service_id,
+                            "idle_secs": idle_secs,
+                            "timeout_secs": timeout
+                        })),
+                        Some(service_id),
+                        None,
+                        None,
+                        ActivitySeverity::Info,
+                    )
+                    .await;
+            }
+        } else if idle_secs + warning_secs >= timeout {
+            if warned.insert(service_id) {
+                app.emit(
+                    REMOTE_EVENT,
+                    RemotesEvent::IdleWarning {
+                        service: service_id,
+                        remaining_secs: timeout - idle_secs,
+                    },
+                )?;
+            }
+        } else {
+            warned.remove(&service_id);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use super::{event::RemotesEvent, state::RemotesState, REMOTE_EVENT};
+
+const SCRAPE_SECONDS: u64 = 15;
+const SCRAPE_TIMEOUT_SECONDS: u64 = 5;
+
+/// Per-tunnel figures taken from cloudflared's Prometheus endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TunnelMetrics {
+    pub service_id: Uuid,
+    pub scraped_at: String,
+    /// `false` for `AccessMode::Access`: `cloudflared access tcp` serves no metrics endpoint,
+    /// so there is nothing to scrape and the figures stay at zero
+    pub available: bool,
+    /// Whether the last scrape succeeded
+    pub reachable: bool,
+    pub total_requests: f64,
+    pub request_errors: f64,
+    /// Active connections from cloudflared to the Cloudflare edge
+    pub ha_connections: f64,
+    pub active_tcp_sessions: f64,
+    pub total_tcp_sessions: f64,
+}
+
+#[derive(Default)]
+pub struct MetricsState {
+    latest: Mutex<HashMap<Uuid, TunnelMetrics>>,
+}
+
+impl MetricsState {
+    pub async fn get(&self, service_id: Option<Uuid>) -> Vec<TunnelMetrics> {
+        self.latest
+            .lock()
+            .await
+            .values()
+            .filter(|m| service_id.is_none_or(|id| id == m.service_id))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Parses the Prometheus text exposition format into `name -> value`, summing samples
+/// that only differ by labels.
+pub fn parse_prometheus(text: &str) -> HashMap<String, f64> {
+    let mut values = HashMap::new();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, rest) = match line.find('{') {
+            Some(brace) => match line[brace..].find('}') {
+                Some(end) => (&line[..brace], &line[brace + end + 1..]),
+                None => continue,
+            },
+            None => match line.split_once(char::is_whitespace) {
+                Some((name, rest)) => (name, rest),
+                None => continue,
+            },
+        };
+
+        let Some(value) = rest
+            .split_whitespace()
+            .next()
+            .and_then(|v| v.parse::<f64>().ok())
+        else {
+            continue;
+        };
+
+        *values.entry(name.to_string()).or_insert(0.0) += value;
+    }
+
+    values
+}
+
+fn to_metrics(service_id: Uuid, values: &HashMap<String, f64>) -> TunnelMetrics {
+    let get = |name: &str| values.get(name).copied().unwrap_or_default();
+
+    TunnelMetrics {
+        service_id,
+        scraped_at: Utc::now().to_rfc3339(),
+        available: true,
+        reachable: true,
+        total_requests: get("cloudflared_tunnel_total_requests"),
+        request_errors: get("cloudflared_tunnel_request_errors"),
+        ha_connections: get("cloudflared_tunnel_ha_connections"),
+        active_tcp_sessions: get("cloudflared_tcp_active_sessions"),
+        total_tcp_sessions: get("cloudflared_tcp_total_sessions"),
+    }
+}
+
+/// Scrapes the metrics endpoint of every connected tunnel and emits `RemotesEvent::Metrics`;
+/// services without an endpoint are reported with `available: false`.
+pub async fn watch(app: AppHandle) {
+    // Loopback only, so the configured proxy must not be used here.
+    let client = match reqwest::Client::builder()
+        .no_proxy()
+        .timeout(Duration::from_secs(SCRAPE_TIMEOUT_SECONDS))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("cannot create metrics client: {e}");
+            return;
+        }
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(SCRAPE_SECONDS));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = scrape(&app, &client).await {
+            warn!("metrics scrape failed: {e}");
+        }
+    }
+}
+
+async fn scrape(app: &AppHandle, client: &reqwest::Client) -> anyhow::Result<()> {
+    let targets = app.state::<RemotesState>().metrics_ports().await;
+    let mut latest = HashMap::new();
+
+    for (service_id, port) in targets {
+        let Some(port) = port else {
+            latest.insert(
+                service_id,
+                TunnelMetrics {
+                    service_id,
+                    scraped_at: Utc::now().to_rfc3339(),
+                    ..Default::default()
+                },
+            );
+            continue;
+        };
+        let url = format!("http://127.0.0.1:{port}/metrics");
+        let metrics = match client.get(&url).send().await {
+            Ok(response) => match response.text().await {
+                Ok(text) => to_metrics(service_id, &parse_prometheus(&text)),
+                Err(e) => unreachable(service_id, e),
+            },
+            Err(e) => unreachable(service_id, e),
+        };
+        latest.insert(service_id, metrics);
+    }
+
+    let snapshot: Vec<TunnelMetrics> = latest.values().cloned().collect();
+    *app.state::<MetricsState>().latest.lock().await = latest;
+
+    if !snapshot.is_empty() {
+        app.emit(REMOTE_EVENT, RemotesEvent::Metrics(snapshot))?;
+    }
+
+    Ok(())
+}
+
+fn unreachable(service_id: Uuid, error: reqwest::Error) -> TunnelMetrics {
+    debug!("metrics for service {service_id} unavailable: {error}");
+    TunnelMetrics {
+        service_id,
+        scraped_at: Utc::now().to_rfc3339(),
+        available: true,
+        reachable: false,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+use tracing::{debug, warn};
+
+use crate::util::PanicMutex;
+
+use super::exposure::SourceAllowlist;
+
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// Traffic bookkeeping shared between a relay and its observers.
+#[derive(Debug)]
+pub struct RelayStats {
+    last_activity: Mutex<Instant>,
+    /// Bytes received from the tunnel and sent to local clients
+    bytes_in: AtomicU64,
+    /// Bytes received from local clients and sent into the tunnel
+    bytes_out: AtomicU64,
+    total_connections: AtomicU64,
+    active_connections: AtomicU64,
+}
+
+impl RelayStats {
+    fn new() -> Self {
+        Self {
+            last_activity: Mutex::new(Instant::now()),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            total_connections: AtomicU64::new(0),
+            active_connections: AtomicU64::new(0),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_activity.lockp() = Instant::now();
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity.lockp().elapsed()
+    }
+
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    pub fn total_connections(&self) -> u64 {
+        self.total_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn active_connections(&self) -> u64 {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+}
+
+/// Local TCP endpoint that forwards every accepted connection to `upstream`
+/// and records when data last went through it. Peers outside `allowlist` are dropped.
+pub struct Relay {
+    pub stats: Arc<RelayStats>,
+    task: tauri::async_runtime::JoinHandle<()>,
+    /// Closes the connections accepted so far when set
+    shutdown: watch::Sender<bool>,
+}
+
+impl Relay {
+    pub async fn bind(
+        listen: SocketAddr,
+        upstream: SocketAddr,
+        allowlist: SourceAllowlist,
+    ) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(listen).await?;
+        let stats = Arc::new(RelayStats::new());
+        let (shutdown, shutdown_rx) = watch::channel(false);
+
+        let stats1 = stats.clone();
+        let task = tauri::async_runtime::spawn(async move {
+            loop {
+                let (inbound, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!(name: "Relay", "accept failed: {e}");
+                        continue;
+                    }
+                };
+                if !allowlist.allows(peer.ip()) {
+                    warn!(name: "Relay", "rejected connection from {peer}: not in allowlist");
+                    continue;
+                }
+                debug!(name: "Relay", "connection from {peer} to {upstream}");
+
+                let stats = stats1.clone();
+                let mut shutdown = shutdown_rx.clone();
+                tauri::async_runtime::spawn(async move {
+                    stats.total_connections.fetch_add(1, Ordering::Relaxed);
+                    stats.active_connections.fetch_add(1, Ordering::Relaxed);
+                    tokio::select! {
+                        result = Self::forward(inbound, upstream, stats.clone()) => {
+                            if let Err(e) = result {
+                                debug!(name: "Relay", "connection from {peer} closed: {e}");
+                            }
+                        }
+                        // A dropped sender means the relay is gone as well
+                        _ = shutdown.wait_for(|stopped| *stopped) => {
+                            debug!(name: "Relay", "connection from {peer} closed: relay stopped");
+                        }
+                    }
+                    stats.active_connections.fetch_sub(1, Ordering::Relaxed);
+                });
+            }
+        });
+
+        Ok(Self {
+            stats,
+            task,
+            shutdown,
+        })
+    }
+
+    async fn forward(
+        inbound: TcpStream,
+        upstream: SocketAddr,
+        stats: Arc<RelayStats>,
+    ) -> anyhow::Result<()> {
+        let outbound = TcpStream::connect(upstream).await?;
+        stats.touch();
+
+        let (mut ri, mut wi) = inbound.into_split();
+        let (mut ro, mut wo) = outbound.into_split();
+
+        let stats1 = stats.clone();
+        let client_to_server = async move {
+            let mut buf = vec![0u8; BUFFER_SIZE];
+            loop {
+                let n = ri.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                wo.write_all(&buf[..n]).await?;
+                stats1.bytes_out.fetch_add(n as u64, Ordering::Relaxed);
+                stats1.touch();
+            }
+            wo.shutdown().await
+        };
+
+        let server_to_client = async move {
+            let mut buf = vec![0u8; BUFFER_SIZE];
+            loop {
+                let n = ro.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                wi.write_all(&buf[..n]).await?;
+                stats.bytes_in.fetch_add(n as u64, Ordering::Relaxed);
+                stats.touch();
+            }
+            wi.shutdown().await
+        };
+
+        tokio::try_join!(client_to_server, server_to_client)?;
+
+        Ok(())
+    }
+
+    /// Stops accepting and closes the open connections, so no session outlives the relay.
+    pub fn stop(self) {
+        self.task.abort();
+        let _ = self.shutdown.send(true);
+    }
+}
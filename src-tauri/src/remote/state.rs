@@ -1,6 +1,8 @@
 use std::{
     collections::{hash_map::Entry, HashMap},
+    net::SocketAddr,
     sync::Arc,
+    time::Duration,
 };
 
 use tauri::{AppHandle, Manager};
@@ -10,9 +12,18 @@ use tokio::sync::Mutex;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use crate::servers::{Credential, Service};
+use crate::{
+    migrations,
+    servers::{AccessMode, Credential, Service},
+};
 
-use super::{cloudflared::Access, handle::RemoteHandle};
+use super::{
+    cloudflared::Access,
+    exposure,
+    handle::RemoteHandle,
+    hooks::{self, HookPoint},
+    relay::RelayStats,
+};
 
 const REMOTES_STORE: &str = "remotes.json";
 const SERVICE_PORTS_KEY: &str = "service_ports";
@@ -48,6 +59,10 @@ impl RemotesState {
             SERVICE_PORTS_KEY,
             serde_json::to_value(HashMap::clone(&service_ports))?,
         );
+        store.set(
+            migrations::SCHEMA_VERSION_KEY,
+            serde_json::json!(migrations::REMOTES.version),
+        );
 
         Ok(())
     }
@@ -58,22 +73,32 @@ impl RemotesState {
         Ok(port)
     }
 
+    /// Local port of the service, allocated and saved on first use so it stays the same.
+    async fn local_port(&self, service_id: Uuid) -> anyhow::Result<u16> {
+        let port = match self.service_ports.lock().await.entry(service_id) {
+            Entry::Occupied(entry) => return Ok(*entry.get()),
+            Entry::Vacant(entry) => *entry.insert(Self::available_port().await?),
+        };
+
+        self.save().await?;
+        Ok(port)
+    }
+
     async fn service_access(&self, app: &AppHandle, service: &Service) -> anyhow::Result<String> {
         match self.service_access.lock().await.entry(service.id) {
             Entry::Occupied(occupied_entry) => Ok(occupied_entry.get().url.clone()),
             Entry::Vacant(vacant_entry) => {
-                let port = match self.service_ports.lock().await.entry(service.id) {
-                    Entry::Occupied(entry) => *entry.get(),
-                    Entry::Vacant(entry) => {
-                        let port = Self::available_port().await?;
-                        entry.insert(port);
-                        port
-                    }
-                };
+                let port = self.local_port(service.id).await?;
 
-                self.save().await?;
-
-                let (mut rx, access) = Access::new(app, service, port)?;
+                let tunnel_port = Self::available_port().await?;
+                // Only `cloudflared tunnel` serves metrics
+                let metrics_port = match service.options.access_mode {
+                    AccessMode::Tunnel => Some(Self::available_port().await?),
+                    AccessMode::Access => None,
+                };
+                let (mut rx, access) =
+                    Access::new(app, service, port, tunnel_port, metrics_port).await?;
+                exposure::report(app, service, access.listen).await;
 
                 let app1 = app.clone();
                 let service_id = service.id;
@@ -115,10 +140,39 @@ impl RemotesState {
         service: &Service,
         credentials: &Credential,
     ) -> anyhow::Result<()> {
+        // Хуки относятся к подъему доступа: для уже запущенного доступа их не вызываем
+        let reused = self.service_access.lock().await.contains_key(&service.id);
+
+        if !reused {
+            // Порт выделяется заранее, чтобы хук pre_connect его уже знал
+            let local_port = self.local_port(service.id).await?;
+            hooks::run(app, HookPoint::PreConnect, service.id, Some(local_port)).await?;
+        }
+
         let url = self.service_access(app, service).await?;
 
-        let (mut rx, handle) =
-            RemoteHandle::new(app, service.id, service.protocol, &url, credentials).await?;
+        if !reused {
+            let local_port = self.service_ports.lock().await.get(&service.id).copied();
+            if let Err(e) = hooks::run(app, HookPoint::PostConnect, service.id, local_port).await {
+                warn!("post_connect hook for service {} failed: {e}", service.id);
+            }
+        }
+
+        let identity_file = service
+            .options
+            .identity_file
+            .as_deref()
+            .map(str::trim)
+            .filter(|f| !f.is_empty());
+        let (mut rx, handle) = RemoteHandle::new(
+            app,
+            service.id,
+            service.protocol,
+            &url,
+            credentials,
+            identity_file,
+        )
+        .await?;
 
         let app1 = app.clone();
         let service_id = service.id;
@@ -160,15 +214,75 @@ impl RemotesState {
         Ok(())
     }
 
+    /// Relay counters of every connected service.
+    pub async fn relay_stats(&self) -> Vec<(Uuid, Arc<RelayStats>)> {
+        self.service_access
+            .lock()
+            .await
+            .iter()
+            .map(|(id, access)| (*id, access.relay.stats.clone()))
+            .collect()
+    }
+
+    /// Metrics endpoint of every connected service, `None` where cloudflared serves none.
+    pub async fn metrics_ports(&self) -> Vec<(Uuid, Option<u16>)> {
+        self.service_access
+            .lock()
+            .await
+            .iter()
+            .map(|(id, access)| (*id, access.metrics_port))
+            .collect()
+    }
+
+    /// cloudflared's own loopback endpoint of each connected service. Unlike the relay it
+    /// does not count connections or reset the idle timer.
+    pub async fn tunnel_endpoints(&self) -> Vec<(Uuid, SocketAddr)> {
+        self.service_access
+            .lock()
+            .await
+            .iter()
+            .map(|(id, access)| (*id, ([127, 0, 0, 1], access.tunnel_port).into()))
+            .collect()
+    }
+
+    pub async fn connected_services(&self) -> Vec<Uuid> {
+        self.service_access.lock().await.keys().copied().collect()
+    }
+
+    /// Time since the last byte went through each connected service's tunnel.
+    pub async fn idle_durations(&self) -> Vec<(Uuid, Duration)> {
+        self.service_access
+            .lock()
+            .await
+            .iter()
+            .map(|(id, access)| (*id, access.relay.stats.idle_for()))
+            .collect()
+    }
+
     pub async fn disconnect_service(&self, service_id: &Uuid) -> anyhow::Result<()> {
-        if let Some(handle) = self.service_handle.lock().await.remove(service_id) {
+        let handle = self.service_handle.lock().await.remove(service_id);
+        let access = self.service_access.lock().await.remove(service_id);
+        let was_connected = handle.is_some() || access.is_some();
+
+        if let Some(handle) = handle {
             handle.stop()?;
         }
 
-        if let Some(access) = self.service_access.lock().await.remove(service_id) {
+        if let Some(access) = access {
             access.stop()?;
         }
 
+        if was_connected {
+            let local_port = self.service_ports.lock().await.get(service_id).copied();
+            let app = self.app.clone();
+            let service_id = *service_id;
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = hooks::run(&app, HookPoint::PostDisconnect, service_id, local_port).await {
+                    warn!("post_disconnect hook for service {service_id} failed: {e}");
+                }
+            });
+        }
+
         Ok(())
     }
 }
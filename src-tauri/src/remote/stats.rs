@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Mutex;
+use tracing::warn;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use super::{event::RemotesEvent, state::RemotesState, REMOTE_EVENT};
+
+const STATS_SECONDS: u64 = 5;
+
+/// Traffic through the local endpoint of a connected service.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ServiceStats {
+    pub service_id: Uuid,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub total_connections: u64,
+    pub active_connections: u64,
+    pub last_activity: String,
+    /// Bytes per second over the last sampling interval
+    pub rate_in: f64,
+    pub rate_out: f64,
+}
+
+#[derive(Default)]
+pub struct StatsState {
+    latest: Mutex<HashMap<Uuid, ServiceStats>>,
+}
+
+impl StatsState {
+    pub async fn get(&self, service_id: Option<Uuid>) -> Vec<ServiceStats> {
+        self.latest
+            .lock()
+            .await
+            .values()
+            .filter(|s| service_id.is_none_or(|id| id == s.service_id))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Samples relay counters and emits `RemotesEvent::Stats` with rolling throughput.
+pub async fn watch(app: AppHandle) {
+    let mut previous: HashMap<Uuid, (Instant, u64, u64)> = HashMap::new();
+    let mut interval = tokio::time::interval(Duration::from_secs(STATS_SECONDS));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = sample(&app, &mut previous).await {
+            warn!("stats sampling failed: {e}");
+        }
+    }
+}
+
+async fn sample(
+    app: &AppHandle,
+    previous: &mut HashMap<Uuid, (Instant, u64, u64)>,
+) -> anyhow::Result<()> {
+    let relays = app.state::<RemotesState>().relay_stats().await;
+    let now = Instant::now();
+    let mut latest = HashMap::new();
+
+    for (service_id, stats) in relays {
+        let bytes_in = stats.bytes_in();
+        let bytes_out = stats.bytes_out();
+
+        let (rate_in, rate_out) = match previous.get(&service_id) {
+            Some((at, prev_in, prev_out)) => {
+                let secs = now.duration_since(*at).as_secs_f64().max(0.001);
+                (
+                    bytes_in.saturating_sub(*prev_in) as f64 / secs,
+                    bytes_out.saturating_sub(*prev_out) as f64 / secs,
+                )
+            }
+            None => (0.0, 0.0),
+        };
+
+        let last_activity = chrono::Duration::from_std(stats.idle_for())
+            .map(|idle| (Utc::now() - idle).to_rfc3339())
+            .unwrap_or_default();
+
+        latest.insert(
+            service_id,
+            ServiceStats {
+                service_id,
+                bytes_in,
+                bytes_out,
+                total_connections: stats.total_connections(),
+                active_connections: stats.active_connections(),
+                last_activity,
+                rate_in,
+                rate_out,
+            },
+        );
+    }
+
+    previous.clear();
+    previous.extend(
+        latest
+            .values()
+            .map(|s| (s.service_id, (now, s.bytes_in, s.bytes_out))),
+    );
+
+    let snapshot: Vec<ServiceStats> = latest.values().cloned().collect();
+    *app.state::<StatsState>().latest.lock().await = latest;
+
+    if !snapshot.is_empty() {
+        app.emit(REMOTE_EVENT, RemotesEvent::Stats(snapshot))?;
+    }
+
+    Ok(())
+}
@@ -4,20 +4,34 @@ use tauri::{AppHandle, Emitter, Event, Manager};
 use tracing::{debug, warn};
 use uuid::Uuid;
 
-pub use credentials::Credential;
+pub use credentials::{AccessServiceToken, Credential};
 pub use models::*;
 pub use state::ServersState;
 
 use crate::util::{invoke, AppHandleExt};
+use validation::Validator;
 
+mod changes;
 mod credentials;
 mod event;
+mod import;
+mod inventory;
 mod models;
+mod probe;
+mod search;
 mod state;
+mod validation;
 
 const SERVERS_EVENT: &str = "servers_event";
 const UPDATE_SECONDS: u64 = 20;
 
+fn service_ids(servers: &[Server<Service>]) -> Vec<Uuid> {
+    servers
+        .iter()
+        .flat_map(|s| s.services.iter().map(|s| s.id))
+        .collect()
+}
+
 #[tauri::command]
 pub async fn toggle_company_expansion(app: AppHandle, company_id: Uuid) -> Result<bool, String> {
     async fn inner(app: AppHandle, company_id: Uuid) -> anyhow::Result<bool> {
@@ -38,11 +52,14 @@ pub async fn toggle_company_expansion(app: AppHandle, company_id: Uuid) -> Resul
 pub async fn load_service_credential(app: AppHandle, service_id: Uuid) -> Result<(), String> {
     async fn inner(app: AppHandle, service_id: Uuid) -> anyhow::Result<()> {
         let servers_state = app.state::<ServersState>();
-        let credential = servers_state
-            .load_service_credential(service_id)
-            .await
-            .ok()
-            .flatten();
+        let credential = match servers_state.load_service_credential(service_id).await {
+            Ok(Some(credential)) => Some(credential),
+            // Нет сохраненных данных: подставляем логин по умолчанию
+            _ => servers_state
+                .get_service(service_id)
+                .await
+                .and_then(|service| Credential::suggested(&service)),
+        };
         let remember = servers_state.is_remembered(service_id).await.unwrap_or(false);
 
         app.emit(
@@ -70,14 +87,171 @@ pub async fn get_service(app: AppHandle, service_id: Uuid) -> Result<Option<Serv
     invoke!(inner, app, service_id).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn has_access_service_token(app: AppHandle, service_id: Uuid) -> Result<bool, String> {
+    async fn inner(app: AppHandle, service_id: Uuid) -> anyhow::Result<bool> {
+        let servers_state = app.state::<ServersState>();
+        servers_state.has_access_service_token(service_id).await
+    }
+
+    invoke!(inner, app, service_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_access_service_token(
+    app: AppHandle,
+    service_id: Uuid,
+    client_id: String,
+    client_secret: String,
+) -> Result<(), String> {
+    async fn inner(
+        app: AppHandle,
+        service_id: Uuid,
+        client_id: String,
+        client_secret: String,
+    ) -> anyhow::Result<()> {
+        let servers_state = app.state::<ServersState>();
+        servers_state
+            .set_access_service_token(service_id, client_id, client_secret)
+            .await
+    }
+
+    invoke!(inner, app, service_id, client_id, client_secret).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_access_service_token(app: AppHandle, service_id: Uuid) -> Result<(), String> {
+    async fn inner(app: AppHandle, service_id: Uuid) -> anyhow::Result<()> {
+        let servers_state = app.state::<ServersState>();
+        servers_state.delete_access_service_token(service_id).await
+    }
+
+    invoke!(inner, app, service_id).map_err(|e| e.to_string())
+}
+
 // CRUD операции для локального управления серверами
+#[tauri::command]
+pub async fn add_company(app: AppHandle, name: String) -> Result<Uuid, String> {
+    async fn inner(app: AppHandle, name: String) -> anyhow::Result<Uuid> {
+        Validator::new().name("name", &name).finish()?;
+
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let company_id = Uuid::new_v4();
+        data.push(models::Company {
+            id: company_id,
+            name: name.trim().to_string(),
+            servers: vec![],
+        });
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+
+        Ok(company_id)
+    }
+
+    invoke!(inner, app, name).map_err(validation::command_error)
+}
+
+#[tauri::command]
+pub async fn update_company(app: AppHandle, company_id: Uuid, name: String) -> Result<(), String> {
+    async fn inner(app: AppHandle, company_id: Uuid, name: String) -> anyhow::Result<()> {
+        Validator::new().name("name", &name).finish()?;
+
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let company = data
+            .iter_mut()
+            .find(|c| c.id == company_id)
+            .ok_or(anyhow::anyhow!("company not found"))?;
+        company.name = name.trim().to_string();
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+
+        Ok(())
+    }
+
+    invoke!(inner, app, company_id, name).map_err(validation::command_error)
+}
+
+/// Удаляет компанию. Серверы непустой компании переносятся в `move_to`,
+/// без него удаление непустой компании запрещено.
+#[tauri::command]
+pub async fn delete_company(
+    app: AppHandle,
+    company_id: Uuid,
+    move_to: Option<Uuid>,
+) -> Result<(), String> {
+    async fn inner(app: AppHandle, company_id: Uuid, move_to: Option<Uuid>) -> anyhow::Result<()> {
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let index = data
+            .iter()
+            .position(|c| c.id == company_id)
+            .ok_or(anyhow::anyhow!("company not found"))?;
+        let mut removed = Vec::new();
+
+        match move_to {
+            Some(target_id) => {
+                if target_id == company_id {
+                    anyhow::bail!("cannot move servers into the company being deleted");
+                }
+                if !data.iter().any(|c| c.id == target_id) {
+                    anyhow::bail!("target company not found");
+                }
+                let company = data.remove(index);
+                let target = data
+                    .iter_mut()
+                    .find(|c| c.id == target_id)
+                    .ok_or(anyhow::anyhow!("target company not found"))?;
+                target.servers.extend(company.servers);
+            }
+            None => {
+                if !data[index].servers.is_empty() {
+                    anyhow::bail!("company is not empty");
+                }
+                removed = service_ids(&data.remove(index).servers);
+            }
+        }
+
+        drop(data);
+        servers_state.save_servers().await?;
+        servers_state.forget_services(&removed).await;
+
+        emit_changes(&app).await?;
+
+        Ok(())
+    }
+
+    invoke!(inner, app, company_id, move_to).map_err(validation::command_error)
+}
+
 #[tauri::command]
 pub async fn add_server(
     app: AppHandle,
     name: String,
     description: Option<String>,
+    company_id: Option<Uuid>,
 ) -> Result<Uuid, String> {
-    async fn inner(app: AppHandle, name: String, description: Option<String>) -> anyhow::Result<Uuid> {
+    async fn inner(
+        app: AppHandle,
+        name: String,
+        description: Option<String>,
+        company_id: Option<Uuid>,
+    ) -> anyhow::Result<Uuid> {
+        Validator::new()
+            .name("name", &name)
+            .description("description", description.as_deref())
+            .finish()?;
+
         let servers_state = app.state::<ServersState>();
         let mut data = servers_state.get_servers_data_mut().await;
         
@@ -85,14 +259,22 @@ pub async fn add_server(
         
         let new_server = models::Server {
             id: server_id,
-            name,
+            name: name.trim().to_string(),
             description,
             services: vec![],
+            tags: vec![],
+            favorite: false,
         };
         
-        // Ищем существующую компанию "Local Servers" или создаем новую
+        // Без явной компании ищем существующую "Local Servers" или создаем новую
         const LOCAL_SERVERS_NAME: &str = "Local Servers";
-        if let Some(company) = data.iter_mut().find(|c| c.name == LOCAL_SERVERS_NAME) {
+        if let Some(company_id) = company_id {
+            data.iter_mut()
+                .find(|c| c.id == company_id)
+                .ok_or(anyhow::anyhow!("company not found"))?
+                .servers
+                .push(new_server);
+        } else if let Some(company) = data.iter_mut().find(|c| c.name == LOCAL_SERVERS_NAME) {
             // Добавляем сервер в существующую компанию
             company.servers.push(new_server);
         } else {
@@ -111,17 +293,12 @@ pub async fn add_server(
         servers_state.save_servers().await?;
         
         // Обновляем UI через событие
-        let servers_state = app.state::<ServersState>();
-        let updated_data = servers_state.get_data().await;
-        app.emit(
-            SERVERS_EVENT,
-            ServersEvent::Updated(updated_data),
-        )?;
+        emit_changes(&app).await?;
         
         Ok(server_id)
     }
     
-    invoke!(inner, app, name, description).map_err(|e| e.to_string())
+    invoke!(inner, app, name, description, company_id).map_err(validation::command_error)
 }
 
 #[tauri::command]
@@ -132,12 +309,17 @@ pub async fn update_server(
     description: Option<String>,
 ) -> Result<(), String> {
     async fn inner(app: AppHandle, server_id: Uuid, name: String, description: Option<String>) -> anyhow::Result<()> {
+        Validator::new()
+            .name("name", &name)
+            .description("description", description.as_deref())
+            .finish()?;
+
         let servers_state = app.state::<ServersState>();
         let mut data = servers_state.get_servers_data_mut().await;
         
         for company in data.iter_mut() {
             if let Some(server) = company.servers.iter_mut().find(|s| s.id == server_id) {
-                server.name = name;
+                server.name = name.trim().to_string();
                 server.description = description;
                 break;
             }
@@ -146,16 +328,12 @@ pub async fn update_server(
         drop(data);
         servers_state.save_servers().await?;
         
-        let servers_state = app.state::<ServersState>();
-        app.emit(
-            SERVERS_EVENT,
-            ServersEvent::Updated(servers_state.get_data().await),
-        )?;
+        emit_changes(&app).await?;
         
         Ok(())
     }
     
-    invoke!(inner, app, server_id, name, description).map_err(|e| e.to_string())
+    invoke!(inner, app, server_id, name, description).map_err(validation::command_error)
 }
 
 #[tauri::command]
@@ -163,20 +341,19 @@ pub async fn delete_server(app: AppHandle, server_id: Uuid) -> Result<(), String
     async fn inner(app: AppHandle, server_id: Uuid) -> anyhow::Result<()> {
         let servers_state = app.state::<ServersState>();
         let mut data = servers_state.get_servers_data_mut().await;
+        let mut removed = Vec::new();
         
         for company in data.iter_mut() {
-            company.servers.retain(|s| s.id != server_id);
+            if let Some(index) = company.servers.iter().position(|s| s.id == server_id) {
+                removed = service_ids(&[company.servers.remove(index)]);
+            }
         }
-        data.retain(|c| !c.servers.is_empty());
         
         drop(data);
         servers_state.save_servers().await?;
+        servers_state.forget_services(&removed).await;
         
-        let servers_state = app.state::<ServersState>();
-        app.emit(
-            SERVERS_EVENT,
-            ServersEvent::Updated(servers_state.get_data().await),
-        )?;
+        emit_changes(&app).await?;
         
         Ok(())
     }
@@ -184,6 +361,54 @@ pub async fn delete_server(app: AppHandle, server_id: Uuid) -> Result<(), String
     invoke!(inner, app, server_id).map_err(|e| e.to_string())
 }
 
+/// Переносит сервер в другую компанию. Без `position` сервер добавляется в конец.
+#[tauri::command]
+pub async fn move_server(
+    app: AppHandle,
+    server_id: Uuid,
+    company_id: Uuid,
+    position: Option<usize>,
+) -> Result<(), String> {
+    async fn inner(
+        app: AppHandle,
+        server_id: Uuid,
+        company_id: Uuid,
+        position: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        if !data.iter().any(|c| c.id == company_id) {
+            anyhow::bail!("company not found");
+        }
+        let server = data
+            .iter_mut()
+            .find_map(|c| {
+                c.servers
+                    .iter()
+                    .position(|s| s.id == server_id)
+                    .map(|i| c.servers.remove(i))
+            })
+            .ok_or(anyhow::anyhow!("server not found"))?;
+
+        let target = data
+            .iter_mut()
+            .find(|c| c.id == company_id)
+            .ok_or(anyhow::anyhow!("company not found"))?;
+        let position = position.unwrap_or(target.servers.len()).min(target.servers.len());
+        target.servers.insert(position, server);
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+
+        Ok(())
+    }
+
+    invoke!(inner, app, server_id, company_id, position).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn add_service(
     app: AppHandle,
@@ -195,14 +420,26 @@ pub async fn add_service(
     async fn inner(app: AppHandle, server_id: Uuid, protocol: models::Protocol, host: String, port: i32) -> anyhow::Result<Uuid> {
         let servers_state = app.state::<ServersState>();
         let mut data = servers_state.get_servers_data_mut().await;
+
+        let server = data
+            .iter()
+            .flat_map(|c| c.servers.iter())
+            .find(|s| s.id == server_id)
+            .ok_or(anyhow::anyhow!("server not found"))?;
+        Validator::new()
+            .endpoint("", &host, port)
+            .unique_endpoint("host", (protocol, &host, port), &server.services, None)
+            .finish()?;
         
         let service_id = Uuid::new_v4();
         let new_service = models::Service {
             id: service_id,
             protocol,
             port,
-            host,
-            status: Some("active".to_string()),
+            host: host.trim().to_string(),
+            options: models::ServiceOptions::default(),
+            tags: vec![],
+            favorite: false,
         };
         
         for company in data.iter_mut() {
@@ -215,16 +452,12 @@ pub async fn add_service(
         drop(data);
         servers_state.save_servers().await?;
         
-        let servers_state = app.state::<ServersState>();
-        app.emit(
-            SERVERS_EVENT,
-            ServersEvent::Updated(servers_state.get_data().await),
-        )?;
+        emit_changes(&app).await?;
         
         Ok(service_id)
     }
     
-    invoke!(inner, app, server_id, protocol, host, port).map_err(|e| e.to_string())
+    invoke!(inner, app, server_id, protocol, host, port).map_err(validation::command_error)
 }
 
 #[tauri::command]
@@ -238,13 +471,23 @@ pub async fn update_service(
     async fn inner(app: AppHandle, service_id: Uuid, protocol: models::Protocol, host: String, port: i32) -> anyhow::Result<()> {
         let servers_state = app.state::<ServersState>();
         let mut data = servers_state.get_servers_data_mut().await;
+
+        let server = data
+            .iter()
+            .flat_map(|c| c.servers.iter())
+            .find(|s| s.services.iter().any(|svc| svc.id == service_id))
+            .ok_or(anyhow::anyhow!("service not found"))?;
+        Validator::new()
+            .endpoint("", &host, port)
+            .unique_endpoint("host", (protocol, &host, port), &server.services, Some(service_id))
+            .finish()?;
         
         let mut found = false;
         for company in data.iter_mut() {
             for server in company.servers.iter_mut() {
                 if let Some(service) = server.services.iter_mut().find(|s| s.id == service_id) {
                     service.protocol = protocol;
-                    service.host = host.clone();
+                    service.host = host.trim().to_string();
                     service.port = port;
                     found = true;
                     break;
@@ -258,16 +501,44 @@ pub async fn update_service(
         drop(data);
         servers_state.save_servers().await?;
         
-        let servers_state = app.state::<ServersState>();
-        app.emit(
-            SERVERS_EVENT,
-            ServersEvent::Updated(servers_state.get_data().await),
-        )?;
+        emit_changes(&app).await?;
         
         Ok(())
     }
     
-    invoke!(inner, app, service_id, protocol, host, port).map_err(|e| e.to_string())
+    invoke!(inner, app, service_id, protocol, host, port).map_err(validation::command_error)
+}
+
+#[tauri::command]
+pub async fn update_service_options(
+    app: AppHandle,
+    service_id: Uuid,
+    options: models::ServiceOptions,
+) -> Result<(), String> {
+    async fn inner(app: AppHandle, service_id: Uuid, options: models::ServiceOptions) -> anyhow::Result<()> {
+        Validator::new().service_options("options", &options).finish()?;
+        crate::remote::check_exposure(&options)?;
+
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let service = data
+            .iter_mut()
+            .flat_map(|c| c.servers.iter_mut())
+            .flat_map(|s| s.services.iter_mut())
+            .find(|s| s.id == service_id)
+            .ok_or(anyhow::anyhow!("service not found"))?;
+        service.options = options;
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+
+        Ok(())
+    }
+
+    invoke!(inner, app, service_id, options).map_err(validation::command_error)
 }
 
 #[tauri::command]
@@ -275,21 +546,23 @@ pub async fn delete_service(app: AppHandle, service_id: Uuid) -> Result<(), Stri
     async fn inner(app: AppHandle, service_id: Uuid) -> anyhow::Result<()> {
         let servers_state = app.state::<ServersState>();
         let mut data = servers_state.get_servers_data_mut().await;
+        let mut removed = false;
         
         for company in data.iter_mut() {
             for server in company.servers.iter_mut() {
+                let count = server.services.len();
                 server.services.retain(|s| s.id != service_id);
+                removed |= server.services.len() != count;
             }
         }
         
         drop(data);
         servers_state.save_servers().await?;
+        if removed {
+            servers_state.forget_services(&[service_id]).await;
+        }
         
-        let servers_state = app.state::<ServersState>();
-        app.emit(
-            SERVERS_EVENT,
-            ServersEvent::Updated(servers_state.get_data().await),
-        )?;
+        emit_changes(&app).await?;
         
         Ok(())
     }
@@ -297,17 +570,479 @@ pub async fn delete_service(app: AppHandle, service_id: Uuid) -> Result<(), Stri
     invoke!(inner, app, service_id).map_err(|e| e.to_string())
 }
 
+/// Переносит сервис на другой сервер. Без `position` сервис добавляется в конец.
+#[tauri::command]
+pub async fn move_service(
+    app: AppHandle,
+    service_id: Uuid,
+    server_id: Uuid,
+    position: Option<usize>,
+) -> Result<(), String> {
+    async fn inner(
+        app: AppHandle,
+        service_id: Uuid,
+        server_id: Uuid,
+        position: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let service = data
+            .iter()
+            .flat_map(|c| c.servers.iter())
+            .flat_map(|s| s.services.iter())
+            .find(|svc| svc.id == service_id)
+            .cloned()
+            .ok_or(anyhow::anyhow!("service not found"))?;
+        let target = data
+            .iter()
+            .flat_map(|c| c.servers.iter())
+            .find(|s| s.id == server_id)
+            .ok_or(anyhow::anyhow!("server not found"))?;
+        // Проверяем до удаления из исходного сервера, чтобы ошибка не теряла сервис
+        Validator::new()
+            .unique_endpoint(
+                "server_id",
+                (service.protocol, &service.host, service.port),
+                &target.services,
+                Some(service.id),
+            )
+            .finish()?;
+
+        for server in data.iter_mut().flat_map(|c| c.servers.iter_mut()) {
+            server.services.retain(|svc| svc.id != service_id);
+        }
+        let target = data
+            .iter_mut()
+            .flat_map(|c| c.servers.iter_mut())
+            .find(|s| s.id == server_id)
+            .ok_or(anyhow::anyhow!("server not found"))?;
+        let position = position.unwrap_or(target.services.len()).min(target.services.len());
+        target.services.insert(position, service);
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+
+        Ok(())
+    }
+
+    invoke!(inner, app, service_id, server_id, position).map_err(validation::command_error)
+}
+
+/// Разбирает ssh config (по умолчанию `~/.ssh/config`) и возвращает найденные хосты
+/// вместе с конфликтами; ничего не сохраняет.
+#[tauri::command]
+pub async fn import_ssh_config(
+    app: AppHandle,
+    path: Option<String>,
+) -> Result<import::ImportPreview, String> {
+    async fn inner(app: AppHandle, path: Option<String>) -> anyhow::Result<import::ImportPreview> {
+        let mut preview = import::ssh_config::parse(path.as_deref())?;
+
+        let servers_state = app.state::<ServersState>();
+        servers_state
+            .hold_import_credentials(preview.take_credentials())
+            .await;
+        let data = servers_state.get_servers_data_mut().await;
+        Ok(preview.with_conflicts(&data))
+    }
+
+    invoke!(inner, app, path).map_err(|e| e.to_string())
+}
+
+/// Разбирает файлы других менеджеров подключений; без `path` берется стандартное расположение.
+#[tauri::command]
+pub async fn import_connections(
+    app: AppHandle,
+    format: import::ImportFormat,
+    path: Option<String>,
+    master_password: Option<String>,
+) -> Result<import::ImportPreview, String> {
+    async fn inner(
+        app: AppHandle,
+        format: import::ImportFormat,
+        path: Option<String>,
+        master_password: Option<String>,
+    ) -> anyhow::Result<import::ImportPreview> {
+        let mut preview = match format {
+            import::ImportFormat::MRemoteNg => {
+                let path = match path {
+                    Some(path) => path,
+                    None => dirs::config_dir()
+                        .ok_or(anyhow::anyhow!("cannot determine config directory"))?
+                        .join("mRemoteNG")
+                        .join("confCons.xml")
+                        .to_string_lossy()
+                        .to_string(),
+                };
+                import::mremoteng::parse(&path, master_password.as_deref())?
+            }
+            import::ImportFormat::Remmina => match path {
+                Some(path) => import::remmina::parse(std::path::Path::new(&path))?,
+                None => {
+                    let mut preview = import::ImportPreview {
+                        candidates: Vec::new(),
+                        warnings: Vec::new(),
+                    };
+                    for dir in import::remmina::default_dirs() {
+                        let found = import::remmina::parse(&dir)?;
+                        preview.candidates.extend(found.candidates);
+                        preview.warnings.extend(found.warnings);
+                    }
+                    preview
+                }
+            },
+            import::ImportFormat::Rdp => {
+                let path = path.ok_or(anyhow::anyhow!("path to a .rdp file or folder is required"))?;
+                import::rdp_file::parse(std::path::Path::new(&path))?
+            }
+        };
+
+        // Расшифрованные пароли остаются в бэкенде до `commit_import`
+        let servers_state = app.state::<ServersState>();
+        servers_state
+            .hold_import_credentials(preview.take_credentials())
+            .await;
+        let data = servers_state.get_servers_data_mut().await;
+        Ok(preview.with_conflicts(&data))
+    }
+
+    invoke!(inner, app, format, path, master_password).map_err(|e| e.to_string())
+}
+
+/// Добавляет выбранные из превью серверы в компанию `company_id`, а без нее —
+/// в компании по папкам источника. Учетные данные сохраняются в keyring только по запросу.
+#[tauri::command]
+pub async fn commit_import(
+    app: AppHandle,
+    company_id: Option<Uuid>,
+    candidates: Vec<import::ImportCandidate>,
+    import_credentials: Option<bool>,
+) -> Result<Vec<Uuid>, String> {
+    async fn inner(
+        app: AppHandle,
+        company_id: Option<Uuid>,
+        mut candidates: Vec<import::ImportCandidate>,
+        import_credentials: Option<bool>,
+    ) -> anyhow::Result<Vec<Uuid>> {
+        import::validate(&candidates)?;
+
+        let servers_state = app.state::<ServersState>();
+        let mut held = servers_state.take_import_credentials().await;
+        if import_credentials.unwrap_or(false) {
+            for service in candidates.iter_mut().flat_map(|c| c.services.iter_mut()) {
+                service.credential = service.credential_id.and_then(|id| held.remove(&id));
+            }
+        }
+
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let created = import::commit(&mut data, company_id, candidates)?;
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        if import_credentials.unwrap_or(false) {
+            for (service, credential) in created.iter().flat_map(|c| c.services.iter()) {
+                let Some(credential) = credential.clone() else {
+                    continue;
+                };
+                if let Err(e) = servers_state
+                    .import_service_credential(service, credential)
+                    .await
+                {
+                    warn!("failed to import credential for service {}: {e}", service.id);
+                }
+            }
+        }
+
+        emit_changes(&app).await?;
+
+        Ok(created.into_iter().map(|c| c.server_id).collect())
+    }
+
+    invoke!(inner, app, company_id, candidates, import_credentials)
+        .map_err(validation::command_error)
+}
+
+/// Сохраняет весь список серверов в файл без учетных данных, хуков и сетевых настроек.
+/// Формат берется из расширения файла, если не указан явно.
+#[tauri::command]
+pub async fn export_inventory(
+    app: AppHandle,
+    path: String,
+    format: Option<inventory::InventoryFormat>,
+) -> Result<(), String> {
+    async fn inner(
+        app: AppHandle,
+        path: String,
+        format: Option<inventory::InventoryFormat>,
+    ) -> anyhow::Result<()> {
+        let path = std::path::PathBuf::from(path);
+        let format = format.unwrap_or_else(|| inventory::InventoryFormat::from_path(&path));
+
+        let servers_state = app.state::<ServersState>();
+        let data = servers_state.get_servers_data_mut().await;
+        let file = inventory::InventoryFile::from_data(&data);
+        drop(data);
+
+        file.write(&path, format)?;
+        debug!("inventory exported to {}", path.display());
+
+        Ok(())
+    }
+
+    invoke!(inner, app, path, format).map_err(|e| e.to_string())
+}
+
+/// Импортирует список серверов из файла. С `dry_run` только возвращает отчет об изменениях.
+/// Хуки и сетевые настройки из файла применяются только с `apply_sensitive_options`.
+#[tauri::command]
+pub async fn import_inventory(
+    app: AppHandle,
+    path: String,
+    mode: inventory::ImportMode,
+    dry_run: Option<bool>,
+    apply_sensitive_options: Option<bool>,
+) -> Result<inventory::InventoryDiff, String> {
+    async fn inner(
+        app: AppHandle,
+        path: String,
+        mode: inventory::ImportMode,
+        dry_run: Option<bool>,
+        apply_sensitive_options: Option<bool>,
+    ) -> anyhow::Result<inventory::InventoryDiff> {
+        let dry_run = dry_run.unwrap_or(false);
+        let apply_sensitive = apply_sensitive_options.unwrap_or(false);
+        let file = inventory::InventoryFile::read(std::path::Path::new(&path))?;
+        file.validate()?;
+        let sensitive = file.sensitive_options();
+
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let merged = inventory::merge(&data, file, mode, apply_sensitive);
+        inventory::validate_merged(&merged)?;
+        let mut diff = inventory::diff(&data, &merged, dry_run);
+        diff.sensitive = sensitive;
+        diff.sensitive_applied = apply_sensitive;
+        if dry_run {
+            return Ok(diff);
+        }
+        *data = merged;
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+
+        Ok(diff)
+    }
+
+    invoke!(inner, app, path, mode, dry_run, apply_sensitive_options)
+        .map_err(validation::command_error)
+}
+
+/// Убирает пустые и повторяющиеся (без учета регистра) теги.
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for tag in tags.into_iter().map(|t| t.trim().to_string()) {
+        if !tag.is_empty() && !result.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            result.push(tag);
+        }
+    }
+    result
+}
+
+/// Отправляет изменения дерева серверов с прошлой публикации одной ревизией.
+async fn emit_changes(app: &AppHandle) -> anyhow::Result<()> {
+    let servers_state = app.state::<ServersState>();
+    if let Some(revision) = servers_state.publish_changes().await {
+        app.emit(SERVERS_EVENT, ServersEvent::Changed(revision))?;
+    }
+    Ok(())
+}
+
+async fn emit_favorites(app: &AppHandle) -> anyhow::Result<()> {
+    let servers_state = app.state::<ServersState>();
+    let (servers, services) = servers_state.list_favorites().await;
+    app.emit(SERVERS_EVENT, ServersEvent::Favorites { servers, services })?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_server_tags(app: AppHandle, server_id: Uuid, tags: Vec<String>) -> Result<(), String> {
+    async fn inner(app: AppHandle, server_id: Uuid, tags: Vec<String>) -> anyhow::Result<()> {
+        Validator::new().tags("tags", &tags).finish()?;
+
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let server = data
+            .iter_mut()
+            .flat_map(|c| c.servers.iter_mut())
+            .find(|s| s.id == server_id)
+            .ok_or(anyhow::anyhow!("server not found"))?;
+        server.tags = normalize_tags(tags);
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+
+        Ok(())
+    }
+
+    invoke!(inner, app, server_id, tags).map_err(validation::command_error)
+}
+
+#[tauri::command]
+pub async fn set_service_tags(app: AppHandle, service_id: Uuid, tags: Vec<String>) -> Result<(), String> {
+    async fn inner(app: AppHandle, service_id: Uuid, tags: Vec<String>) -> anyhow::Result<()> {
+        Validator::new().tags("tags", &tags).finish()?;
+
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let service = data
+            .iter_mut()
+            .flat_map(|c| c.servers.iter_mut())
+            .flat_map(|s| s.services.iter_mut())
+            .find(|s| s.id == service_id)
+            .ok_or(anyhow::anyhow!("service not found"))?;
+        service.tags = normalize_tags(tags);
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+
+        Ok(())
+    }
+
+    invoke!(inner, app, service_id, tags).map_err(validation::command_error)
+}
+
+#[tauri::command]
+pub async fn set_server_favorite(app: AppHandle, server_id: Uuid, favorite: bool) -> Result<(), String> {
+    async fn inner(app: AppHandle, server_id: Uuid, favorite: bool) -> anyhow::Result<()> {
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let server = data
+            .iter_mut()
+            .flat_map(|c| c.servers.iter_mut())
+            .find(|s| s.id == server_id)
+            .ok_or(anyhow::anyhow!("server not found"))?;
+        server.favorite = favorite;
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+        emit_favorites(&app).await?;
+
+        Ok(())
+    }
+
+    invoke!(inner, app, server_id, favorite).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_service_favorite(app: AppHandle, service_id: Uuid, favorite: bool) -> Result<(), String> {
+    async fn inner(app: AppHandle, service_id: Uuid, favorite: bool) -> anyhow::Result<()> {
+        let servers_state = app.state::<ServersState>();
+        let mut data = servers_state.get_servers_data_mut().await;
+
+        let service = data
+            .iter_mut()
+            .flat_map(|c| c.servers.iter_mut())
+            .flat_map(|s| s.services.iter_mut())
+            .find(|s| s.id == service_id)
+            .ok_or(anyhow::anyhow!("service not found"))?;
+        service.favorite = favorite;
+
+        drop(data);
+        servers_state.save_servers().await?;
+
+        emit_changes(&app).await?;
+        emit_favorites(&app).await?;
+
+        Ok(())
+    }
+
+    invoke!(inner, app, service_id, favorite).map_err(|e| e.to_string())
+}
+
+/// Нечеткий поиск по компаниям, серверам, хостам, портам, протоколам и тегам.
+#[tauri::command]
+pub async fn search_services(
+    app: AppHandle,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<search::SearchHit>, String> {
+    async fn inner(
+        app: AppHandle,
+        query: String,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<search::SearchHit>> {
+        let servers_state = app.state::<ServersState>();
+        let data = servers_state.get_servers_data_mut().await;
+        Ok(search::search(&data, &query, limit))
+    }
+
+    invoke!(inner, app, query, limit).map_err(|e| e.to_string())
+}
+
+/// Результаты фоновой проверки доступности; без `service_id` — по всем сервисам.
+#[tauri::command]
+pub async fn get_service_health(
+    app: AppHandle,
+    service_id: Option<Uuid>,
+) -> Result<Vec<probe::ServiceHealth>, String> {
+    async fn inner(
+        app: AppHandle,
+        service_id: Option<Uuid>,
+    ) -> anyhow::Result<Vec<probe::ServiceHealth>> {
+        let servers_state = app.state::<ServersState>();
+        Ok(servers_state.list_health(service_id).await)
+    }
+
+    invoke!(inner, app, service_id).map_err(|e| e.to_string())
+}
+
+/// Догоняет пропущенные ревизии; без `revision` или для слишком старой — полный снимок.
+#[tauri::command]
+pub async fn get_servers_since(
+    app: AppHandle,
+    revision: Option<u64>,
+) -> Result<changes::ServersSync, String> {
+    async fn inner(app: AppHandle, revision: Option<u64>) -> anyhow::Result<changes::ServersSync> {
+        let servers_state = app.state::<ServersState>();
+        Ok(servers_state.get_since(revision).await)
+    }
+
+    invoke!(inner, app, revision).map_err(|e| e.to_string())
+}
+
 async fn on_ui_ready(app: AppHandle, _event: Event) {
     async fn inner(app: AppHandle) -> anyhow::Result<()> {
         let servers_state = app.state::<ServersState>();
+        let (revision, companies) = servers_state.get_snapshot().await;
         app.emit(
             SERVERS_EVENT,
-            ServersEvent::Updated(servers_state.get_data().await),
+            ServersEvent::Updated {
+                revision,
+                companies,
+            },
         )?;
         app.emit(
             SERVERS_EVENT,
             ServersEvent::Expanded(servers_state.list_expanded_companies().await),
         )?;
+        emit_favorites(&app).await?;
         Ok(())
     }
 
@@ -321,5 +1056,7 @@ pub fn setup(app: &AppHandle) -> anyhow::Result<()> {
 
     app.listen_async(crate::UI_READY_EVENT, on_ui_ready);
 
+    tauri::async_runtime::spawn(probe::watch(app.clone()));
+
     Ok(())
 }
@@ -0,0 +1,363 @@
+//! Granular change events for the server tree.
+//!
+//! Instead of re-sending the whole tree after every command, the state keeps the last
+//! published tree and diffs it against the current one. Each non-empty diff gets the next
+//! revision number and is kept in a short journal, so a client that missed events can catch
+//! up with `get_servers_since` or fall back to a full snapshot.
+//!
+//! Changes of one revision are ordered so that applying them one by one is enough:
+//! additions and updates first (parents before children, moved items are appended to their
+//! new parent), then removals, then the final order of every list whose order differs.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use super::{
+    models::{
+        Company, ErasedService, ErasedServiceCompanies, InnerCompanyServices, Server, Service,
+    },
+    probe::ServiceHealth,
+};
+
+/// How many revisions `get_servers_since` can replay before falling back to a snapshot
+const JOURNAL_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export)]
+pub struct CompanyInfo {
+    pub id: Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export)]
+pub struct ServerInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub favorite: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum ServersChange {
+    CompanyAdded(CompanyInfo),
+    CompanyUpdated(CompanyInfo),
+    CompanyRemoved(Uuid),
+    CompaniesReordered(Vec<Uuid>),
+    /// Followed by `ServiceAdded` for each of its services
+    ServerAdded {
+        company_id: Uuid,
+        server: ServerInfo,
+    },
+    /// Also sent when the server moved to another company
+    ServerUpdated {
+        company_id: Uuid,
+        server: ServerInfo,
+    },
+    ServerRemoved(Uuid),
+    ServersReordered {
+        company_id: Uuid,
+        server_ids: Vec<Uuid>,
+    },
+    ServiceAdded {
+        server_id: Uuid,
+        service: ErasedService,
+    },
+    /// Also sent when the service moved to another server
+    ServiceUpdated {
+        server_id: Uuid,
+        service: ErasedService,
+    },
+    ServiceRemoved(Uuid),
+    ServicesReordered {
+        server_id: Uuid,
+        service_ids: Vec<Uuid>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ServersRevision {
+    pub revision: u64,
+    pub changes: Vec<ServersChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum ServersSync {
+    /// Revisions after the requested one, oldest first
+    Changes(Vec<ServersRevision>),
+    /// The requested revision is unknown or too old to replay
+    Snapshot {
+        revision: u64,
+        companies: ErasedServiceCompanies,
+    },
+}
+
+impl From<&Company<Service>> for CompanyInfo {
+    fn from(company: &Company<Service>) -> Self {
+        CompanyInfo {
+            id: company.id,
+            name: company.name.clone(),
+        }
+    }
+}
+
+impl From<&Server<Service>> for ServerInfo {
+    fn from(server: &Server<Service>) -> Self {
+        ServerInfo {
+            id: server.id,
+            name: server.name.clone(),
+            description: server.description.clone(),
+            tags: server.tags.clone(),
+            favorite: server.favorite,
+        }
+    }
+}
+
+/// Converts the tree for the UI, attaching the latest reachability results.
+pub fn erase(
+    data: &InnerCompanyServices,
+    health: &HashMap<Uuid, ServiceHealth>,
+) -> ErasedServiceCompanies {
+    let mut companies: ErasedServiceCompanies = data.iter().cloned().map(Into::into).collect();
+    for service in companies
+        .iter_mut()
+        .flat_map(|c| c.servers.iter_mut())
+        .flat_map(|s| s.services.iter_mut())
+    {
+        service.set_health(health.get(&service.id).cloned());
+    }
+    companies
+}
+
+/// Order a client ends up with after removals and appends: surviving items keep their old
+/// order, new and moved-in ones follow in their new order.
+fn expected_order(old: &[Uuid], new: &[Uuid]) -> Vec<Uuid> {
+    let mut order: Vec<Uuid> = old.iter().filter(|id| new.contains(id)).copied().collect();
+    order.extend(new.iter().filter(|id| !old.contains(id)));
+    order
+}
+
+pub fn diff(
+    old: &InnerCompanyServices,
+    new: &InnerCompanyServices,
+    health: &HashMap<Uuid, ServiceHealth>,
+) -> Vec<ServersChange> {
+    let old_companies: HashMap<Uuid, &Company<Service>> = old.iter().map(|c| (c.id, c)).collect();
+    let old_servers: HashMap<Uuid, (Uuid, &Server<Service>)> = old
+        .iter()
+        .flat_map(|c| c.servers.iter().map(move |s| (s.id, (c.id, s))))
+        .collect();
+    let old_services: HashMap<Uuid, (Uuid, &Service)> = old
+        .iter()
+        .flat_map(|c| c.servers.iter())
+        .flat_map(|s| s.services.iter().map(move |v| (v.id, (s.id, v))))
+        .collect();
+
+    let erase_service = |service: &Service| {
+        let mut erased = ErasedService::from(service.clone());
+        erased.set_health(health.get(&service.id).cloned());
+        erased
+    };
+
+    let mut changes = Vec::new();
+    let mut reorders = Vec::new();
+
+    for company in new {
+        match old_companies.get(&company.id) {
+            None => changes.push(ServersChange::CompanyAdded(company.into())),
+            Some(old) if old.name != company.name => {
+                changes.push(ServersChange::CompanyUpdated(company.into()))
+            }
+            Some(_) => {}
+        }
+
+        for server in &company.servers {
+            match old_servers.get(&server.id) {
+                None => changes.push(ServersChange::ServerAdded {
+                    company_id: company.id,
+                    server: server.into(),
+                }),
+                Some((old_company, old))
+                    if *old_company != company.id
+                        || ServerInfo::from(*old) != ServerInfo::from(server) =>
+                {
+                    changes.push(ServersChange::ServerUpdated {
+                        company_id: company.id,
+                        server: server.into(),
+                    })
+                }
+                Some(_) => {}
+            }
+
+            for service in &server.services {
+                match old_services.get(&service.id) {
+                    None => changes.push(ServersChange::ServiceAdded {
+                        server_id: server.id,
+                        service: erase_service(service),
+                    }),
+                    Some((old_server, old)) if *old_server != server.id || *old != service => {
+                        changes.push(ServersChange::ServiceUpdated {
+                            server_id: server.id,
+                            service: erase_service(service),
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            let service_ids: Vec<Uuid> = server.services.iter().map(|s| s.id).collect();
+            let old_ids: Vec<Uuid> = old_servers
+                .get(&server.id)
+                .map(|(_, s)| s.services.iter().map(|s| s.id).collect())
+                .unwrap_or_default();
+            if expected_order(&old_ids, &service_ids) != service_ids {
+                reorders.push(ServersChange::ServicesReordered {
+                    server_id: server.id,
+                    service_ids,
+                });
+            }
+        }
+
+        let server_ids: Vec<Uuid> = company.servers.iter().map(|s| s.id).collect();
+        let old_ids: Vec<Uuid> = old_companies
+            .get(&company.id)
+            .map(|c| c.servers.iter().map(|s| s.id).collect())
+            .unwrap_or_default();
+        if expected_order(&old_ids, &server_ids) != server_ids {
+            reorders.push(ServersChange::ServersReordered {
+                company_id: company.id,
+                server_ids,
+            });
+        }
+    }
+
+    let new_companies: Vec<Uuid> = new.iter().map(|c| c.id).collect();
+    let new_servers: HashSet<Uuid> = new.iter().flat_map(|c| &c.servers).map(|s| s.id).collect();
+    let new_services: HashSet<Uuid> = new
+        .iter()
+        .flat_map(|c| &c.servers)
+        .flat_map(|s| &s.services)
+        .map(|s| s.id)
+        .collect();
+
+    // Removing a parent removes whatever is still inside it, so only the topmost removal is sent
+    for company in old {
+        for server in &company.servers {
+            if !new_servers.contains(&server.id) {
+                continue;
+            }
+            for service in &server.services {
+                if !new_services.contains(&service.id) {
+                    changes.push(ServersChange::ServiceRemoved(service.id));
+                }
+            }
+        }
+    }
+    for company in old.iter().filter(|c| new_companies.contains(&c.id)) {
+        for server in &company.servers {
+            if !new_servers.contains(&server.id) {
+                changes.push(ServersChange::ServerRemoved(server.id));
+            }
+        }
+    }
+    for company in old {
+        if !new_companies.contains(&company.id) {
+            changes.push(ServersChange::CompanyRemoved(company.id));
+        }
+    }
+
+    let old_company_ids: Vec<Uuid> = old.iter().map(|c| c.id).collect();
+    if expected_order(&old_company_ids, &new_companies) != new_companies {
+        changes.push(ServersChange::CompaniesReordered(new_companies));
+    }
+    changes.extend(reorders);
+
+    changes
+}
+
+/// The tree as last sent to the UI plus the recent revisions.
+///
+/// Revisions start from the launch time in milliseconds rather than from zero, so a revision
+/// the UI kept from before a restart is older than any of this launch and gets a snapshot.
+pub struct Journal {
+    revision: u64,
+    published: InnerCompanyServices,
+    entries: VecDeque<ServersRevision>,
+}
+
+impl Journal {
+    pub fn new(published: InnerCompanyServices) -> Self {
+        Journal {
+            revision: chrono::Utc::now().timestamp_millis().max(0) as u64,
+            published,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Records the difference to `current` as the next revision, if there is any.
+    pub fn publish(
+        &mut self,
+        current: &InnerCompanyServices,
+        health: &HashMap<Uuid, ServiceHealth>,
+    ) -> Option<ServersRevision> {
+        let changes = diff(&self.published, current, health);
+        if changes.is_empty() {
+            return None;
+        }
+
+        self.revision += 1;
+        self.published = current.clone();
+        let entry = ServersRevision {
+            revision: self.revision,
+            changes,
+        };
+        self.entries.push_back(entry.clone());
+        while self.entries.len() > JOURNAL_LENGTH {
+            self.entries.pop_front();
+        }
+
+        Some(entry)
+    }
+
+    pub fn since(
+        &self,
+        revision: Option<u64>,
+        health: &HashMap<Uuid, ServiceHealth>,
+    ) -> ServersSync {
+        if let Some(revision) = revision {
+            let oldest = self
+                .entries
+                .front()
+                .map_or(self.revision + 1, |e| e.revision);
+            // Revisions are consecutive, so the journal covers everything after `oldest - 1`;
+            // one ahead of the current revision is unknown and gets a snapshot as well
+            if revision <= self.revision && revision + 1 >= oldest {
+                return ServersSync::Changes(
+                    self.entries
+                        .iter()
+                        .filter(|e| e.revision > revision)
+                        .cloned()
+                        .collect(),
+                );
+            }
+        }
+
+        let (revision, companies) = self.snapshot(health);
+        ServersSync::Snapshot {
+            revision,
+            companies,
+        }
+    }
+
+    /// The published tree and its revision, so changes can be followed from there.
+    pub fn snapshot(&self, health: &HashMap<Uuid, ServiceHealth>) -> (u64, ErasedServiceCompanies) {
+        (self.revision, erase(&self.published, health))
+    }
+}
@@ -52,6 +52,41 @@ impl Credential {
         }
     }
 
+    /// Empty credential with the service's default login, to prefill the prompt.
+    pub fn suggested(service: &Service) -> Option<Self> {
+        let login = service
+            .options
+            .default_login
+            .as_deref()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())?
+            .to_string();
+        Some(match service.protocol {
+            Protocol::Rdp => Credential::RdpUserPassword {
+                login,
+                password: String::default(),
+                domain: String::default(),
+            },
+            Protocol::Ssh => Credential::SshUserPassword {
+                login,
+                password: String::default(),
+            },
+        })
+    }
+
+    /// SSH services with a key file and a default login connect without a prompt.
+    pub fn from_identity_file(service: &Service) -> Option<Self> {
+        let has_key = service
+            .options
+            .identity_file
+            .as_deref()
+            .is_some_and(|f| !f.trim().is_empty());
+        if service.protocol != Protocol::Ssh || !has_key {
+            return None;
+        }
+        Self::suggested(service)
+    }
+
     fn from_json(value: Value) -> anyhow::Result<Self> {
         serde_json::from_value(value).context("Failed to deserialize credential from JSON")
     }
@@ -171,4 +206,78 @@ impl ServiceCredential {
         self.remember = false;
         Ok(())
     }
+
+    /// Removes the stored credential of a service that no longer exists.
+    pub fn delete(service_id: Uuid) -> anyhow::Result<()> {
+        match Entry::new(KEYRING_SERVICE, &service_id.to_string())?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {
+                debug!("deleted credential");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Cloudflare Access service token used by cloudflared instead of a browser login.
+/// Stored in the OS keyring under `<service id>.access-token`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AccessServiceToken {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl AccessServiceToken {
+    fn entry(service_id: Uuid) -> anyhow::Result<Entry> {
+        Ok(Entry::new(
+            KEYRING_SERVICE,
+            &format!("{}.access-token", service_id),
+        )?)
+    }
+
+    pub fn load(service_id: Uuid) -> anyhow::Result<Option<Self>> {
+        match Self::entry(service_id)?.get_password() {
+            Ok(secret) => Ok(Some(
+                serde_json::from_str(&secret).context("Failed to parse Access service token")?,
+            )),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, service_id: Uuid) -> anyhow::Result<()> {
+        let secret = serde_json::to_string(self)
+            .context("Failed to serialize Access service token")?;
+        Self::entry(service_id)?
+            .set_password(&secret)
+            .context("Failed to store Access service token in keyring")?;
+        debug!("saved access service token");
+        Ok(())
+    }
+
+    pub fn delete(service_id: Uuid) -> anyhow::Result<()> {
+        match Self::entry(service_id)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {
+                debug!("deleted access service token");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Environment for cloudflared; keeps the secret out of the process arguments.
+    pub fn envs(&self) -> [(&'static str, String); 2] {
+        [
+            ("TUNNEL_SERVICE_TOKEN_ID", self.client_id.clone()),
+            ("TUNNEL_SERVICE_TOKEN_SECRET", self.client_secret.clone()),
+        ]
+    }
+}
+
+impl std::fmt::Debug for AccessServiceToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessServiceToken")
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
 }
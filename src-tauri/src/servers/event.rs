@@ -2,13 +2,28 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use uuid::Uuid;
 
-use super::{credentials::Credential, models::ErasedServiceCompanies};
+use super::{
+    changes::ServersRevision, credentials::Credential, models::ErasedServiceCompanies,
+    probe::ServiceHealth,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub enum ServersEvent {
-    Updated(ErasedServiceCompanies),
+    /// Full tree as of `revision`, sent when the UI starts
+    Updated {
+        revision: u64,
+        companies: ErasedServiceCompanies,
+    },
+    /// What changed since the previous revision
+    Changed(ServersRevision),
     Expanded(Vec<Uuid>),
+    Favorites {
+        servers: Vec<Uuid>,
+        services: Vec<Uuid>,
+    },
+    /// Only the services whose reachability changed since the previous check
+    Health(Vec<ServiceHealth>),
     ServiceCredential {
         service: Uuid,
         remember: bool,
@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use super::{
+    credentials::Credential,
+    inventory::keep_sensitive,
+    models::{Company, InnerCompanyServices, Protocol, Server, Service, ServiceOptions},
+    validation::{path, Validator},
+};
+
+pub mod mremoteng;
+pub mod rdp_file;
+pub mod remmina;
+pub mod ssh_config;
+
+/// Used for candidates without a folder when no company is chosen.
+const IMPORTED_COMPANY_NAME: &str = "Imported";
+
+/// Connection managers whose exports can be imported.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum ImportFormat {
+    /// mRemoteNG `confCons.xml`
+    MRemoteNg,
+    /// A Remmina profile or a directory of `.remmina` files
+    Remmina,
+    /// A `.rdp` file or a directory of them
+    Rdp,
+}
+
+/// A service found in an external inventory, not yet added to the list.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportedService {
+    pub protocol: Protocol,
+    pub host: String,
+    pub port: i32,
+    pub options: ServiceOptions,
+    /// Saved credentials found by the parser. Never sent to the UI, see `take_credentials`
+    #[serde(skip)]
+    #[ts(skip)]
+    pub credential: Option<Credential>,
+    /// Handle of the saved credentials kept by the backend until `commit_import`
+    #[serde(default)]
+    pub credential_id: Option<Uuid>,
+}
+
+/// A server found in an external inventory together with what it collides with.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportCandidate {
+    /// Company from the source's folder structure, used when no company is chosen
+    #[serde(default)]
+    pub company: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub services: Vec<ImportedService>,
+    /// Where the entry was found, e.g. `~/.ssh/config:12`
+    pub source: String,
+    pub conflicts: Vec<ImportConflict>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum ImportConflict {
+    /// A server with the same name already exists
+    ServerName { server_id: Uuid },
+    /// A service with the same protocol, host and port already exists
+    ServiceEndpoint { service_id: Uuid },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ImportPreview {
+    pub candidates: Vec<ImportCandidate>,
+    /// Entries that were skipped or could not be read
+    pub warnings: Vec<String>,
+}
+
+impl ImportPreview {
+    /// Moves saved credentials out of the preview and leaves a handle in their place,
+    /// so decrypted passwords stay in the backend.
+    pub fn take_credentials(&mut self) -> HashMap<Uuid, Credential> {
+        let mut credentials = HashMap::new();
+        for service in self.candidates.iter_mut().flat_map(|c| c.services.iter_mut()) {
+            if let Some(credential) = service.credential.take() {
+                let id = Uuid::new_v4();
+                credentials.insert(id, credential);
+                service.credential_id = Some(id);
+            }
+        }
+        credentials
+    }
+
+    /// Marks candidates that collide with existing servers or services.
+    pub fn with_conflicts(mut self, data: &InnerCompanyServices) -> Self {
+        for candidate in &mut self.candidates {
+            candidate.conflicts = conflicts(data, &candidate.name, &candidate.services);
+        }
+        self
+    }
+}
+
+fn conflicts(
+    data: &InnerCompanyServices,
+    name: &str,
+    services: &[ImportedService],
+) -> Vec<ImportConflict> {
+    let servers = data.iter().flat_map(|c| c.servers.iter());
+    let mut conflicts: Vec<ImportConflict> = servers
+        .clone()
+        .filter(|s| s.name.eq_ignore_ascii_case(name))
+        .map(|s| ImportConflict::ServerName { server_id: s.id })
+        .collect();
+
+    for existing in servers.flat_map(|s| s.services.iter()) {
+        if services.iter().any(|imported| {
+            imported.protocol == existing.protocol
+                && imported.port == existing.port
+                && imported.host.eq_ignore_ascii_case(&existing.host)
+        }) {
+            conflicts.push(ImportConflict::ServiceEndpoint {
+                service_id: existing.id,
+            });
+        }
+    }
+
+    conflicts
+}
+
+/// Splits `host`, `host:port` or `[v6]:port`.
+pub fn split_host_port(address: &str, default_port: i32) -> Option<(String, i32)> {
+    let address = address.trim();
+    if address.is_empty() {
+        return None;
+    }
+
+    if let Some(rest) = address.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        let port = match rest.strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None => default_port,
+        };
+        return Some((host.to_string(), port));
+    }
+
+    match address.rsplit_once(':') {
+        // A bare IPv6 address has more than one colon
+        Some((host, port)) if !host.contains(':') => Some((host.to_string(), port.parse().ok()?)),
+        _ => Some((address.to_string(), default_port)),
+    }
+}
+
+/// Files with `extension` at `path` (or `path` itself) and their folder relative to it,
+/// joined with ` / ` for use as a company name.
+pub fn collect_files(path: &Path, extension: &str) -> Vec<(PathBuf, Option<String>)> {
+    fn walk(dir: &Path, folders: &[String], extension: &str, found: &mut Vec<(PathBuf, Option<String>)>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                let mut folders = folders.to_vec();
+                folders.push(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                walk(&path, &folders, extension, found);
+            } else if path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+            {
+                let folder = (!folders.is_empty()).then(|| folders.join(" / "));
+                found.push((path, folder));
+            }
+        }
+    }
+
+    if path.is_file() {
+        return vec![(path.to_path_buf(), None)];
+    }
+    let mut found = Vec::new();
+    walk(path, &[], extension, &mut found);
+    found
+}
+
+/// A server created by [`commit`] with the ids and credentials of its services.
+pub struct Committed {
+    pub server_id: Uuid,
+    pub services: Vec<(Service, Option<Credential>)>,
+}
+
+/// Checks names, endpoints and options of the candidates; fields are reported as
+/// `candidates[i]...`.
+pub fn validate(candidates: &[ImportCandidate]) -> anyhow::Result<()> {
+    let mut validator = Validator::new();
+    for (i, candidate) in candidates.iter().enumerate() {
+        let prefix = format!("candidates[{i}]");
+        validator
+            .name(path(&prefix, "name"), &candidate.name)
+            .description(path(&prefix, "description"), candidate.description.as_deref());
+        if let Some(company) = &candidate.company {
+            validator.name(path(&prefix, "company"), company);
+        }
+        for (j, service) in candidate.services.iter().enumerate() {
+            let prefix = path(&prefix, &format!("services[{j}]"));
+            validator
+                .endpoint(&prefix, &service.host, service.port)
+                .service_options(&path(&prefix, "options"), &service.options);
+        }
+        let endpoints: Vec<(Protocol, &str, i32)> = candidate
+            .services
+            .iter()
+            .map(|s| (s.protocol, s.host.as_str(), s.port))
+            .collect();
+        validator.unique_endpoints(&prefix, &endpoints);
+    }
+    validator.finish()
+}
+
+/// Appends the selected candidates to `company_id`, or to companies named after their folders
+/// when no company is chosen. Missing companies are created at the end of the list.
+pub fn commit(
+    data: &mut InnerCompanyServices,
+    company_id: Option<Uuid>,
+    candidates: Vec<ImportCandidate>,
+) -> anyhow::Result<Vec<Committed>> {
+    if let Some(company_id) = company_id {
+        if !data.iter().any(|c| c.id == company_id) {
+            anyhow::bail!("company not found");
+        }
+    }
+
+    let mut created = Vec::new();
+    for candidate in candidates {
+        let company: &mut Company<Service> = match company_id {
+            Some(company_id) => data
+                .iter_mut()
+                .find(|c| c.id == company_id)
+                .ok_or(anyhow::anyhow!("company not found"))?,
+            None => {
+                let name = candidate
+                    .company
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|n| !n.is_empty())
+                    .unwrap_or(IMPORTED_COMPANY_NAME);
+                match data.iter().position(|c| c.name == name) {
+                    Some(index) => &mut data[index],
+                    None => {
+                        data.push(Company {
+                            id: Uuid::new_v4(),
+                            name: name.to_string(),
+                            servers: vec![],
+                        });
+                        data.last_mut().expect("company was just added")
+                    }
+                }
+            }
+        };
+
+        let services: Vec<(Service, Option<Credential>)> = candidate
+            .services
+            .into_iter()
+            .map(|mut s| {
+                // Хуки и доступ из сети импорт не задает, их настраивают только вручную
+                keep_sensitive(&mut s.options, &ServiceOptions::default());
+                (
+                    Service {
+                        id: Uuid::new_v4(),
+                        protocol: s.protocol,
+                        port: s.port,
+                        host: s.host,
+                        options: s.options,
+                        tags: vec![],
+                        favorite: false,
+                    },
+                    s.credential,
+                )
+            })
+            .collect();
+
+        let server_id = Uuid::new_v4();
+        company.servers.push(Server {
+            id: server_id,
+            name: candidate.name,
+            description: candidate.description,
+            services: services.iter().map(|(s, _)| s.clone()).collect(),
+            tags: vec![],
+            favorite: false,
+        });
+        created.push(Committed {
+            server_id,
+            services,
+        });
+    }
+
+    Ok(created)
+}
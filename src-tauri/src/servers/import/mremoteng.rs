@@ -0,0 +1,227 @@
+//! Reads mRemoteNG `confCons.xml`.
+//!
+//! Containers become companies (nested folders are joined with ` / `), connections become
+//! servers with a single service. Passwords are decrypted with the file's master password,
+//! which is `mR3m` unless the user set one in mRemoteNG.
+
+use std::fs;
+
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, KeyInit};
+use aes_gcm::{aead::Aead, aead::Payload, aes::Aes256, AesGcm};
+use anyhow::Context;
+use base64::Engine;
+
+use crate::servers::{
+    credentials::Credential,
+    models::{Protocol, ServiceOptions},
+};
+
+use super::{ImportCandidate, ImportPreview, ImportedService};
+
+const DEFAULT_MASTER_PASSWORD: &str = "mR3m";
+const DEFAULT_KDF_ITERATIONS: u32 = 1000;
+const FOLDER_SEPARATOR: &str = " / ";
+
+/// mRemoteNG uses a 128-bit GCM nonce instead of the usual 96 bits.
+type Aes256Gcm16 = AesGcm<Aes256, aes_gcm::aead::consts::U16>;
+
+enum Cipher {
+    /// mRemoteNG 1.76+: AES-256-GCM, key from PBKDF2-HMAC-SHA1, `salt | nonce | ciphertext | tag`
+    Gcm { iterations: u32 },
+    /// Older releases: AES-128-CBC keyed with MD5 of the password, `iv | ciphertext`
+    LegacyCbc,
+}
+
+impl Cipher {
+    fn decrypt(&self, password: &str, text: &str) -> anyhow::Result<String> {
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(text.trim())
+            .context("encrypted value is not base64")?;
+
+        let plain = match self {
+            Cipher::Gcm { iterations } => {
+                if data.len() < 48 {
+                    anyhow::bail!("encrypted value is too short");
+                }
+                let (salt, rest) = data.split_at(16);
+                let (nonce, ciphertext) = rest.split_at(16);
+
+                let mut key = [0u8; 32];
+                pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password.as_bytes(), salt, *iterations, &mut key);
+
+                Aes256Gcm16::new(GenericArray::from_slice(&key))
+                    .decrypt(
+                        GenericArray::from_slice(nonce),
+                        Payload {
+                            msg: ciphertext,
+                            aad: salt,
+                        },
+                    )
+                    .map_err(|_| anyhow::anyhow!("wrong master password or corrupted value"))?
+            }
+            Cipher::LegacyCbc => {
+                if data.len() < 32 || data.len() % 16 != 0 {
+                    anyhow::bail!("encrypted value has an invalid length");
+                }
+                let (iv, ciphertext) = data.split_at(16);
+                let cipher = aes::Aes128::new(GenericArray::from_slice(&md5::compute(password).0));
+
+                let mut previous = iv.to_vec();
+                let mut plain = Vec::with_capacity(ciphertext.len());
+                for chunk in ciphertext.chunks(16) {
+                    let mut block = GenericArray::clone_from_slice(chunk);
+                    cipher.decrypt_block(&mut block);
+                    plain.extend(block.iter().zip(&previous).map(|(b, p)| b ^ p));
+                    previous = chunk.to_vec();
+                }
+
+                let pad = *plain.last().unwrap_or(&0) as usize;
+                if pad == 0 || pad > 16 || plain[plain.len() - pad..].iter().any(|b| *b as usize != pad) {
+                    anyhow::bail!("wrong master password or corrupted value");
+                }
+                plain.truncate(plain.len() - pad);
+                plain
+            }
+        };
+
+        String::from_utf8(plain).context("decrypted value is not UTF-8")
+    }
+}
+
+struct Reader<'a> {
+    cipher: Cipher,
+    password: &'a str,
+    path: String,
+    candidates: Vec<ImportCandidate>,
+    warnings: Vec<String>,
+}
+
+impl Reader<'_> {
+    fn walk(&mut self, node: roxmltree::Node, folders: &[String]) {
+        for child in node.children().filter(|n| n.has_tag_name("Node")) {
+            let name = child.attribute("Name").unwrap_or_default().to_string();
+            match child.attribute("Type") {
+                Some("Container") => {
+                    let mut folders = folders.to_vec();
+                    folders.push(name);
+                    self.walk(child, &folders);
+                }
+                Some("Connection") => self.connection(child, name, folders),
+                other => self
+                    .warnings
+                    .push(format!("{}: {name}: unsupported node type {other:?}", self.path)),
+            }
+        }
+    }
+
+    fn connection(&mut self, node: roxmltree::Node, name: String, folders: &[String]) {
+        let attr = |key: &str| {
+            node.attribute(key)
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+
+        let (protocol, default_port) = match attr("Protocol").as_deref() {
+            Some("RDP") => (Protocol::Rdp, 3389),
+            Some("SSH1") | Some("SSH2") => (Protocol::Ssh, 22),
+            other => {
+                self.warnings
+                    .push(format!("{}: {name}: unsupported protocol {other:?}", self.path));
+                return;
+            }
+        };
+        let Some(host) = attr("Hostname") else {
+            self.warnings
+                .push(format!("{}: {name}: no hostname", self.path));
+            return;
+        };
+        let port = attr("Port")
+            .and_then(|p| p.parse::<i32>().ok())
+            .filter(|p| *p > 0)
+            .unwrap_or(default_port);
+
+        let login = attr("Username");
+        let domain = attr("Domain").unwrap_or_default();
+        let password = match attr("Password") {
+            Some(encrypted) => match self.cipher.decrypt(self.password, &encrypted) {
+                Ok(password) => password,
+                Err(e) => {
+                    self.warnings
+                        .push(format!("{}: {name}: password not imported: {e}", self.path));
+                    String::new()
+                }
+            },
+            None => String::new(),
+        };
+
+        let credential = login.clone().map(|login| match protocol {
+            Protocol::Rdp => Credential::RdpUserPassword {
+                login,
+                password,
+                domain,
+            },
+            Protocol::Ssh => Credential::SshUserPassword { login, password },
+        });
+
+        self.candidates.push(ImportCandidate {
+            company: (!folders.is_empty()).then(|| folders.join(FOLDER_SEPARATOR)),
+            name,
+            description: attr("Descr"),
+            services: vec![ImportedService {
+                protocol,
+                host,
+                port,
+                options: ServiceOptions {
+                    default_login: login,
+                    ..Default::default()
+                },
+                credential,
+                credential_id: None,
+            }],
+            source: self.path.clone(),
+            conflicts: Vec::new(),
+        });
+    }
+}
+
+pub fn parse(path: &str, master_password: Option<&str>) -> anyhow::Result<ImportPreview> {
+    let text = fs::read_to_string(path).with_context(|| format!("cannot read {path}"))?;
+    let document = roxmltree::Document::parse(&text).context("invalid confCons.xml")?;
+    let root = document.root_element();
+
+    let cipher = match root.attribute("BlockCipherMode") {
+        Some(_) => Cipher::Gcm {
+            iterations: root
+                .attribute("KdfIterations")
+                .and_then(|i| i.parse().ok())
+                .unwrap_or(DEFAULT_KDF_ITERATIONS),
+        },
+        None => Cipher::LegacyCbc,
+    };
+    let mut reader = Reader {
+        cipher,
+        password: master_password.unwrap_or(DEFAULT_MASTER_PASSWORD),
+        path: path.to_string(),
+        candidates: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    if root.attribute("FullFileEncryption") == Some("true") {
+        let encrypted = root.text().unwrap_or_default();
+        let decrypted = reader
+            .cipher
+            .decrypt(reader.password, encrypted)
+            .context("cannot decrypt confCons.xml")?;
+        let inner = format!("<Connections>{decrypted}</Connections>");
+        let document = roxmltree::Document::parse(&inner).context("invalid decrypted confCons.xml")?;
+        reader.walk(document.root_element(), &[]);
+    } else {
+        reader.walk(root, &[]);
+    }
+
+    Ok(ImportPreview {
+        candidates: reader.candidates,
+        warnings: reader.warnings,
+    })
+}
@@ -0,0 +1,113 @@
+//! Reads Microsoft `.rdp` connection files.
+//!
+//! Saved passwords (`password 51:b:`) are DPAPI blobs bound to the Windows account
+//! that created them and are not imported.
+
+use std::{fs, path::Path};
+
+use crate::servers::{
+    credentials::Credential,
+    models::{Protocol, ServiceOptions},
+};
+
+use super::{collect_files, split_host_port, ImportCandidate, ImportPreview, ImportedService};
+
+const RDP_EXTENSION: &str = "rdp";
+const DEFAULT_RDP_PORT: i32 = 3389;
+
+/// `.rdp` files written by mstsc are UTF-16LE with a BOM.
+fn decode(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+/// `name:type:value` pairs, e.g. `full address:s:host:3390`.
+fn setting<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    text.lines().find_map(|line| {
+        let (key, rest) = line.trim().split_once(':')?;
+        let (_, value) = rest.split_once(':')?;
+        (key.eq_ignore_ascii_case(name) && !value.trim().is_empty()).then(|| value.trim())
+    })
+}
+
+pub fn parse(path: &Path) -> anyhow::Result<ImportPreview> {
+    let mut candidates = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (file, folder) in collect_files(path, RDP_EXTENSION) {
+        let source = file.to_string_lossy().to_string();
+        let text = match fs::read(&file) {
+            Ok(bytes) => decode(&bytes),
+            Err(e) => {
+                warnings.push(format!("{source}: {e}"));
+                continue;
+            }
+        };
+
+        let default_port = setting(&text, "server port")
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(DEFAULT_RDP_PORT);
+        let Some((host, port)) = setting(&text, "full address")
+            .or_else(|| setting(&text, "alternate full address"))
+            .and_then(|a| split_host_port(a, default_port))
+        else {
+            warnings.push(format!("{source}: no full address"));
+            continue;
+        };
+
+        // `DOMAIN\user` and `user@domain` both carry the domain in the user name
+        let mut domain = setting(&text, "domain").unwrap_or_default().to_string();
+        let login = setting(&text, "username").map(|user| match user.split_once('\\') {
+            Some((d, user)) if domain.is_empty() => {
+                domain = d.to_string();
+                user.to_string()
+            }
+            _ => user.to_string(),
+        });
+        if setting(&text, "password 51").is_some() {
+            warnings.push(format!("{source}: saved password not imported"));
+        }
+
+        let credential = login.clone().map(|login| Credential::RdpUserPassword {
+            login,
+            password: String::new(),
+            domain: domain.clone(),
+        });
+
+        candidates.push(ImportCandidate {
+            company: folder,
+            name: file
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| host.clone()),
+            description: None,
+            services: vec![ImportedService {
+                protocol: Protocol::Rdp,
+                host,
+                port,
+                options: ServiceOptions {
+                    default_login: login,
+                    ..Default::default()
+                },
+                credential,
+                credential_id: None,
+            }],
+            source,
+            conflicts: Vec::new(),
+        });
+    }
+
+    Ok(ImportPreview {
+        candidates,
+        warnings,
+    })
+}
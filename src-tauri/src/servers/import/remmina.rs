@@ -0,0 +1,134 @@
+//! Reads Remmina `.remmina` profiles.
+//!
+//! The `group` key (nested with `/`) becomes the company. Remmina keeps passwords either
+//! 3DES-encrypted with a per-install secret or in the desktop keyring, so only logins are imported.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::servers::{
+    credentials::Credential,
+    models::{Protocol, ServiceOptions},
+};
+
+use super::{collect_files, split_host_port, ImportCandidate, ImportPreview, ImportedService};
+
+const PROFILE_EXTENSION: &str = "remmina";
+
+/// Remmina's data directories, newest layout first.
+pub fn default_dirs() -> Vec<std::path::PathBuf> {
+    [
+        dirs::data_dir().map(|d| d.join("remmina")),
+        dirs::home_dir().map(|d| d.join(".remmina")),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|d| d.is_dir())
+    .collect()
+}
+
+fn read_profile(path: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let text = fs::read_to_string(path)?;
+    let mut values = HashMap::new();
+    let mut in_section = false;
+
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_section = line == "[remmina]";
+            continue;
+        }
+        if !in_section || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    Ok(values)
+}
+
+pub fn parse(path: &Path) -> anyhow::Result<ImportPreview> {
+    let mut candidates = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (file, folder) in collect_files(path, PROFILE_EXTENSION) {
+        let source = file.to_string_lossy().to_string();
+        let profile = match read_profile(&file) {
+            Ok(profile) => profile,
+            Err(e) => {
+                warnings.push(format!("{source}: {e}"));
+                continue;
+            }
+        };
+        let get = |key: &str| profile.get(key).filter(|v| !v.is_empty()).cloned();
+
+        let (protocol, default_port) = match get("protocol").as_deref() {
+            Some("RDP") => (Protocol::Rdp, 3389),
+            Some("SSH") | Some("SFTP") => (Protocol::Ssh, 22),
+            other => {
+                warnings.push(format!("{source}: unsupported protocol {other:?}"));
+                continue;
+            }
+        };
+        let Some((host, port)) = get("server").and_then(|s| split_host_port(&s, default_port)) else {
+            warnings.push(format!("{source}: no server address"));
+            continue;
+        };
+
+        let login = match protocol {
+            Protocol::Ssh => get("username").or_else(|| get("ssh_username")),
+            Protocol::Rdp => get("username"),
+        };
+        if get("password").is_some() {
+            warnings.push(format!("{source}: encrypted password not imported"));
+        }
+
+        let credential = login.clone().map(|login| match protocol {
+            Protocol::Rdp => Credential::RdpUserPassword {
+                login,
+                password: String::new(),
+                domain: get("domain").unwrap_or_default(),
+            },
+            Protocol::Ssh => Credential::SshUserPassword {
+                login,
+                password: String::new(),
+            },
+        });
+
+        let company = get("group")
+            .map(|g| {
+                g.split('/')
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" / ")
+            })
+            .filter(|g| !g.is_empty())
+            .or(folder);
+
+        candidates.push(ImportCandidate {
+            company,
+            name: get("name").unwrap_or_else(|| host.clone()),
+            description: None,
+            services: vec![ImportedService {
+                protocol,
+                host,
+                port,
+                options: ServiceOptions {
+                    default_login: login,
+                    identity_file: get("ssh_privatekey"),
+                    ..Default::default()
+                },
+                credential,
+                credential_id: None,
+            }],
+            source,
+            conflicts: Vec::new(),
+        });
+    }
+
+    Ok(ImportPreview {
+        candidates,
+        warnings,
+    })
+}
@@ -0,0 +1,355 @@
+//! Reads `Host` blocks from OpenSSH client configs.
+//!
+//! Options are resolved the way `ssh` does it: for every concrete alias all matching
+//! `Host` blocks are applied in file order and the first value of each keyword wins.
+//! `Match` blocks cannot be evaluated without a connection and are skipped.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::servers::models::{Protocol, ServiceOptions};
+
+use super::{ImportCandidate, ImportPreview, ImportedService};
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+const DEFAULT_SSH_PORT: i32 = 22;
+
+#[derive(Debug, Default)]
+struct Block {
+    /// `None` for the global section before the first `Host`, which applies to every host
+    patterns: Option<Vec<String>>,
+    /// `Match` blocks are kept so their options do not leak into the previous block
+    skipped: bool,
+    options: Vec<(String, String)>,
+    source: String,
+}
+
+impl Block {
+    fn matches(&self, alias: &str) -> bool {
+        if self.skipped {
+            return false;
+        }
+        let Some(patterns) = &self.patterns else {
+            return true;
+        };
+
+        let mut matched = false;
+        for pattern in patterns {
+            match pattern.strip_prefix('!') {
+                Some(negated) if wildcard_match(negated, alias) => return false,
+                Some(_) => {}
+                None => matched |= wildcard_match(pattern, alias),
+            }
+        }
+        matched
+    }
+
+    fn get(&self, keyword: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == keyword)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// `*` and `?` globbing as used by `Host` patterns and `Include` file names.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((bp, bt)) = backtrack {
+            p = bp + 1;
+            t = bt + 1;
+            backtrack = Some((bp, bt + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn is_wildcard(pattern: &str) -> bool {
+    pattern.starts_with('!') || pattern.contains(['*', '?'])
+}
+
+/// Splits a config line into its keyword and arguments, honouring `key=value` and double quotes.
+fn split_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let split = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let keyword = line[..split].to_lowercase();
+    let rest = line[split..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut has_arg = false;
+    for c in rest.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                has_arg = true;
+            }
+            '#' if !quoted && !has_arg => break,
+            c if c.is_whitespace() && !quoted => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_arg = true;
+            }
+        }
+    }
+    if has_arg {
+        args.push(current);
+    }
+
+    Some((keyword, args))
+}
+
+fn home_dir() -> anyhow::Result<PathBuf> {
+    dirs::home_dir().ok_or(anyhow::anyhow!("cannot determine home directory"))
+}
+
+fn expand_tilde(path: &str) -> anyhow::Result<PathBuf> {
+    match path.strip_prefix("~/") {
+        Some(rest) => Ok(home_dir()?.join(rest)),
+        None if path == "~" => home_dir(),
+        None => Ok(PathBuf::from(path)),
+    }
+}
+
+/// Resolves an `Include` argument; relative paths are taken from `~/.ssh` like `ssh` does
+/// for user configs. Only the file name may contain wildcards.
+fn include_paths(pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let path = expand_tilde(pattern)?;
+    let path = if path.is_relative() {
+        home_dir()?.join(".ssh").join(path)
+    } else {
+        path
+    };
+
+    let Some(name) = path.file_name().and_then(|n| n.to_str()).map(str::to_string) else {
+        return Ok(Vec::new());
+    };
+    if !name.contains(['*', '?']) {
+        return Ok(vec![path]);
+    }
+
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(Vec::new());
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| wildcard_match(&name, n))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+struct Parser {
+    blocks: Vec<Block>,
+    warnings: Vec<String>,
+}
+
+impl Parser {
+    fn parse_file(&mut self, path: &Path, depth: usize) -> anyhow::Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            self.warnings
+                .push(format!("{}: Include nested too deeply", path.display()));
+            return Ok(());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+
+        for (number, line) in text.lines().enumerate() {
+            let Some((keyword, args)) = split_line(line) else {
+                continue;
+            };
+            let source = format!("{}:{}", path.display(), number + 1);
+
+            match keyword.as_str() {
+                "host" => self.blocks.push(Block {
+                    patterns: Some(args),
+                    source,
+                    ..Default::default()
+                }),
+                "match" => {
+                    self.warnings.push(format!("{source}: Match block skipped"));
+                    self.blocks.push(Block {
+                        skipped: true,
+                        source,
+                        ..Default::default()
+                    });
+                }
+                "include" => {
+                    let (patterns, skipped) = self
+                        .blocks
+                        .last()
+                        .map(|b| (b.patterns.clone(), b.skipped))
+                        .unwrap_or_default();
+                    for pattern in &args {
+                        for include in include_paths(pattern)? {
+                            if let Err(e) = self.parse_file(&include, depth + 1) {
+                                self.warnings.push(format!("{source}: {e}"));
+                            }
+                        }
+                    }
+                    // Options after an Include still belong to the enclosing block
+                    self.blocks.push(Block {
+                        patterns,
+                        skipped,
+                        source,
+                        ..Default::default()
+                    });
+                }
+                _ => {
+                    let Some(value) = args.first() else {
+                        continue;
+                    };
+                    if self.blocks.is_empty() {
+                        self.blocks.push(Block {
+                            source: source.clone(),
+                            ..Default::default()
+                        });
+                    }
+                    if let Some(block) = self.blocks.last_mut() {
+                        let value = match keyword.as_str() {
+                            // Keep every jump host, `ProxyJump a b` is not valid but `a,b` is
+                            "proxyjump" => args.join(","),
+                            _ => value.clone(),
+                        };
+                        block.options.push((keyword, value));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, alias: &str) -> impl Fn(&str) -> Option<String> + '_ {
+        let alias = alias.to_string();
+        move |keyword: &str| {
+            self.blocks
+                .iter()
+                .filter(|b| b.matches(&alias))
+                .find_map(|b| b.get(keyword))
+                .map(|v| v.replace("%h", &alias).replace("%%", "%"))
+        }
+    }
+
+    fn candidates(&self, warnings: &mut Vec<String>) -> Vec<ImportCandidate> {
+        let mut aliases: Vec<(String, String)> = Vec::new();
+        for block in &self.blocks {
+            for pattern in block.patterns.iter().flatten() {
+                if !is_wildcard(pattern) && !aliases.iter().any(|(a, _)| a == pattern) {
+                    aliases.push((pattern.clone(), block.source.clone()));
+                }
+            }
+        }
+
+        let mut candidates = Vec::new();
+        for (alias, source) in aliases {
+            let get = self.resolve(&alias);
+            let host = get("hostname").unwrap_or_else(|| alias.clone());
+            let port = match get("port") {
+                Some(port) => match port.parse::<i32>() {
+                    Ok(port) if (1..=65535).contains(&port) => port,
+                    _ => {
+                        warnings.push(format!("{source}: invalid Port {port:?} for {alias}"));
+                        continue;
+                    }
+                },
+                None => DEFAULT_SSH_PORT,
+            };
+            let identity_file = get("identityfile").map(|f| {
+                expand_tilde(&f)
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or(f)
+            });
+            let proxy_jump = get("proxyjump").filter(|j| !j.eq_ignore_ascii_case("none"));
+            if let Some(jump) = &proxy_jump {
+                warnings.push(format!(
+                    "{source}: ProxyJump {jump} for {alias} is not used, connections go through cloudflared"
+                ));
+            }
+
+            candidates.push(ImportCandidate {
+                company: None,
+                name: alias.clone(),
+                description: None,
+                services: vec![ImportedService {
+                    protocol: Protocol::Ssh,
+                    host,
+                    port,
+                    options: ServiceOptions {
+                        default_login: get("user"),
+                        identity_file,
+                        proxy_jump,
+                        ..Default::default()
+                    },
+                    credential: None,
+                    credential_id: None,
+                }],
+                source,
+                conflicts: Vec::new(),
+            });
+        }
+
+        candidates
+    }
+}
+
+/// Parses the config at `path` (`~/.ssh/config` when unset) into import candidates.
+pub fn parse(path: Option<&str>) -> anyhow::Result<ImportPreview> {
+    let path = match path {
+        Some(path) => expand_tilde(path)?,
+        None => home_dir()?.join(".ssh").join("config"),
+    };
+
+    let mut parser = Parser {
+        blocks: Vec::new(),
+        warnings: Vec::new(),
+    };
+    parser.parse_file(&path, 0)?;
+
+    let mut warnings = std::mem::take(&mut parser.warnings);
+    let candidates = parser.candidates(&mut warnings);
+
+    Ok(ImportPreview {
+        candidates,
+        warnings,
+    })
+}
@@ -0,0 +1,675 @@
+//! Portable server inventory.
+//!
+//! ```yaml
+//! schema: argo-inventory
+//! version: 1
+//! exported_at: 2025-01-31T12:00:00+00:00
+//! companies:
+//!   - id: 8c3b…           # optional on import, a new id is generated when missing
+//!     name: Production
+//!     servers:
+//!       - id: 1f0e…
+//!         name: db-1
+//!         description: null
+//!         tags: [postgres]
+//!         favorite: false
+//!         services:
+//!           - id: 77aa…
+//!             protocol: ssh   # ssh | rdp
+//!             host: 10.0.0.5
+//!             port: 22
+//!             options: {}     # ServiceOptions, every field optional
+//!             tags: []
+//!             favorite: true
+//! ```
+//!
+//! Credentials and Access service tokens live in the OS keyring and are never exported.
+//! Neither are hooks, `bind_address` and `allowed_sources`: they run commands or open the
+//! endpoint to the network, so an import only applies them when explicitly asked to and
+//! otherwise keeps the current values.
+
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use super::{
+    models::{
+        Company, ConnectionHooks, InnerCompanyServices, Protocol, Server, Service, ServiceOptions,
+    },
+    validation::{path, Validator},
+};
+
+pub const INVENTORY_SCHEMA: &str = "argo-inventory";
+pub const INVENTORY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum InventoryFormat {
+    Json,
+    Yaml,
+}
+
+impl InventoryFormat {
+    /// `.yaml`/`.yml` files are YAML, everything else is JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                Self::Yaml
+            }
+            _ => Self::Json,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryFile {
+    pub schema: String,
+    pub version: u32,
+    #[serde(default)]
+    pub exported_at: Option<String>,
+    pub companies: Vec<InventoryCompany>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryCompany {
+    #[serde(default)]
+    pub id: Option<Uuid>,
+    pub name: String,
+    #[serde(default)]
+    pub servers: Vec<InventoryServer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryServer {
+    #[serde(default)]
+    pub id: Option<Uuid>,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub services: Vec<InventoryService>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub favorite: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryService {
+    #[serde(default)]
+    pub id: Option<Uuid>,
+    pub protocol: Protocol,
+    pub host: String,
+    pub port: i32,
+    #[serde(default)]
+    pub options: ServiceOptions,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub favorite: bool,
+}
+
+/// Names of the options in `options` that are only imported on request.
+fn sensitive_fields(options: &ServiceOptions) -> Vec<String> {
+    let mut fields = Vec::new();
+    if options.hooks != ConnectionHooks::default() {
+        fields.push("hooks".to_string());
+    }
+    if options.bind_address.as_deref().is_some_and(|a| !a.trim().is_empty()) {
+        fields.push("bind_address".to_string());
+    }
+    if !options.allowed_sources.is_empty() {
+        fields.push("allowed_sources".to_string());
+    }
+    fields
+}
+
+/// Replaces the sensitive options of `options` with those of `keep`.
+pub(super) fn keep_sensitive(options: &mut ServiceOptions, keep: &ServiceOptions) {
+    options.hooks = keep.hooks.clone();
+    options.bind_address = keep.bind_address.clone();
+    options.allowed_sources = keep.allowed_sources.clone();
+}
+
+impl InventoryFile {
+    pub fn from_data(data: &InnerCompanyServices) -> Self {
+        Self {
+            schema: INVENTORY_SCHEMA.to_string(),
+            version: INVENTORY_VERSION,
+            exported_at: Some(Utc::now().to_rfc3339()),
+            companies: data
+                .iter()
+                .map(|c| InventoryCompany {
+                    id: Some(c.id),
+                    name: c.name.clone(),
+                    servers: c
+                        .servers
+                        .iter()
+                        .map(|s| InventoryServer {
+                            id: Some(s.id),
+                            name: s.name.clone(),
+                            description: s.description.clone(),
+                            services: s
+                                .services
+                                .iter()
+                                .map(|svc| InventoryService {
+                                    id: Some(svc.id),
+                                    protocol: svc.protocol,
+                                    host: svc.host.clone(),
+                                    port: svc.port,
+                                    options: {
+                                        let mut options = svc.options.clone();
+                                        keep_sensitive(&mut options, &ServiceOptions::default());
+                                        options
+                                    },
+                                    tags: svc.tags.clone(),
+                                    favorite: svc.favorite,
+                                })
+                                .collect(),
+                            tags: s.tags.clone(),
+                            favorite: s.favorite,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn write(&self, path: &Path, format: InventoryFormat) -> anyhow::Result<()> {
+        let text = match format {
+            InventoryFormat::Json => serde_json::to_string_pretty(self)?,
+            InventoryFormat::Yaml => serde_yaml::to_string(self)?,
+        };
+        fs::write(path, text).with_context(|| format!("cannot write {}", path.display()))
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+        let file: Self = match InventoryFormat::from_path(path) {
+            InventoryFormat::Json => {
+                serde_json::from_str(&text).context("invalid inventory file")?
+            }
+            InventoryFormat::Yaml => {
+                serde_yaml::from_str(&text).context("invalid inventory file")?
+            }
+        };
+
+        if file.schema != INVENTORY_SCHEMA {
+            anyhow::bail!("not an inventory file: schema is {:?}", file.schema);
+        }
+        if file.version == 0 || file.version > INVENTORY_VERSION {
+            anyhow::bail!(
+                "unsupported inventory version {} (supported up to {INVENTORY_VERSION})",
+                file.version
+            );
+        }
+
+        Ok(file)
+    }
+
+    /// Services that set options applied only with `apply_sensitive_options`.
+    pub fn sensitive_options(&self) -> Vec<SensitiveOptions> {
+        self.companies
+            .iter()
+            .flat_map(|c| c.servers.iter())
+            .flat_map(|s| s.services.iter().map(move |svc| (s, svc)))
+            .filter_map(|(server, service)| {
+                let fields = sensitive_fields(&service.options);
+                (!fields.is_empty()).then(|| SensitiveOptions {
+                    server: server.name.clone(),
+                    service: format!("{}:{}", service.host, service.port),
+                    fields,
+                })
+            })
+            .collect()
+    }
+
+    /// Checks names, endpoints, tags and options; fields are reported as
+    /// `companies[i].servers[j]...`.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut validator = Validator::new();
+        for (i, company) in self.companies.iter().enumerate() {
+            let prefix = format!("companies[{i}]");
+            validator.name(path(&prefix, "name"), &company.name);
+
+            for (j, server) in company.servers.iter().enumerate() {
+                let prefix = path(&prefix, &format!("servers[{j}]"));
+                validator
+                    .name(path(&prefix, "name"), &server.name)
+                    .description(path(&prefix, "description"), server.description.as_deref())
+                    .tags(&path(&prefix, "tags"), &server.tags);
+                for (k, service) in server.services.iter().enumerate() {
+                    let prefix = path(&prefix, &format!("services[{k}]"));
+                    validator
+                        .endpoint(&prefix, &service.host, service.port)
+                        .service_options(&path(&prefix, "options"), &service.options)
+                        .tags(&path(&prefix, "tags"), &service.tags);
+                }
+                let endpoints: Vec<(Protocol, &str, i32)> = server
+                    .services
+                    .iter()
+                    .map(|s| (s.protocol, s.host.as_str(), s.port))
+                    .collect();
+                validator.unique_endpoints(&prefix, &endpoints);
+            }
+        }
+        validator.finish()
+    }
+}
+
+/// How imported entries are combined with the current list.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum ImportMode {
+    /// The file replaces the whole list
+    Replace,
+    /// Entries with a known id are updated in place, the rest are added
+    MergeById,
+    /// Companies and servers are matched by name, services by protocol, host and port
+    MergeByNameHost,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum InventoryEntity {
+    Company,
+    Server,
+    Service,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum InventoryChangeKind {
+    Added,
+    Updated,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct InventoryChange {
+    pub kind: InventoryChangeKind,
+    pub entity: InventoryEntity,
+    pub id: Uuid,
+    /// Company or server name, `host:port` for services
+    pub name: String,
+}
+
+/// A service in the file with hooks or network exposure settings.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SensitiveOptions {
+    pub server: String,
+    /// `host:port`
+    pub service: String,
+    /// `hooks`, `bind_address`, `allowed_sources`
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct InventoryDiff {
+    pub dry_run: bool,
+    pub changes: Vec<InventoryChange>,
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    /// Services whose sensitive options are in the file
+    pub sensitive: Vec<SensitiveOptions>,
+    /// Whether those options were applied; otherwise the current values were kept
+    pub sensitive_applied: bool,
+}
+
+/// Returns `id` unless it is missing or already taken, in which case a new one is generated.
+fn fresh_id(id: Option<Uuid>, taken: &mut Vec<Uuid>) -> Uuid {
+    let id = id
+        .filter(|id| !taken.contains(id))
+        .unwrap_or_else(Uuid::new_v4);
+    taken.push(id);
+    id
+}
+
+fn all_ids(data: &InnerCompanyServices) -> Vec<Uuid> {
+    data.iter()
+        .flat_map(|c| {
+            std::iter::once(c.id).chain(
+                c.servers
+                    .iter()
+                    .flat_map(|s| std::iter::once(s.id).chain(s.services.iter().map(|svc| svc.id))),
+            )
+        })
+        .collect()
+}
+
+fn find_service(data: &InnerCompanyServices, id: Uuid) -> Option<(usize, usize, usize)> {
+    data.iter().enumerate().find_map(|(ci, c)| {
+        c.servers.iter().enumerate().find_map(|(si, s)| {
+            s.services
+                .iter()
+                .position(|svc| svc.id == id)
+                .map(|vi| (ci, si, vi))
+        })
+    })
+}
+
+/// Builds a service from the file. Unless `apply_sensitive` is set, sensitive options come
+/// from `previous`, the service it replaces, or are left unset for a new one.
+fn to_service(
+    service: InventoryService,
+    id: Uuid,
+    previous: Option<&Service>,
+    apply_sensitive: bool,
+) -> Service {
+    let mut options = service.options;
+    if !apply_sensitive {
+        let default = ServiceOptions::default();
+        keep_sensitive(&mut options, previous.map_or(&default, |p| &p.options));
+    }
+
+    Service {
+        id,
+        protocol: service.protocol,
+        port: service.port,
+        host: service.host,
+        options,
+        tags: service.tags,
+        favorite: service.favorite,
+    }
+}
+
+/// Adds `services` to the server at `target`, updating the ones that already exist.
+fn merge_services(
+    data: &mut InnerCompanyServices,
+    target: (usize, usize),
+    services: Vec<InventoryService>,
+    mode: ImportMode,
+    taken: &mut Vec<Uuid>,
+    apply_sensitive: bool,
+) {
+    let (ci, si) = target;
+    for service in services {
+        let location = match mode {
+            ImportMode::MergeByNameHost => data[ci].servers[si]
+                .services
+                .iter()
+                .position(|existing| {
+                    existing.protocol == service.protocol
+                        && existing.port == service.port
+                        && existing.host.eq_ignore_ascii_case(&service.host)
+                })
+                .map(|vi| (ci, si, vi)),
+            // By id the service may be on another server, then it moves here
+            _ => service.id.and_then(|id| find_service(data, id)),
+        };
+
+        match location {
+            Some((c, s, v)) => {
+                let previous = data[c].servers[s].services.remove(v);
+                let service = to_service(service, previous.id, Some(&previous), apply_sensitive);
+                let services = &mut data[ci].servers[si].services;
+                if (c, s) == target {
+                    services.insert(v, service);
+                } else {
+                    services.push(service);
+                }
+            }
+            None => {
+                let id = fresh_id(service.id, taken);
+                data[ci].servers[si]
+                    .services
+                    .push(to_service(service, id, None, apply_sensitive));
+            }
+        }
+    }
+}
+
+/// Builds the list that results from importing `file` into `current`. Sensitive options
+/// of the file are applied only with `apply_sensitive`.
+pub fn merge(
+    current: &InnerCompanyServices,
+    file: InventoryFile,
+    mode: ImportMode,
+    apply_sensitive: bool,
+) -> InnerCompanyServices {
+    let mut data = match mode {
+        ImportMode::Replace => Vec::new(),
+        _ => current.clone(),
+    };
+    let mut taken = all_ids(&data);
+
+    for company in file.companies {
+        let index = match mode {
+            ImportMode::Replace => None,
+            ImportMode::MergeByNameHost => data
+                .iter()
+                .position(|c| c.name.eq_ignore_ascii_case(&company.name)),
+            ImportMode::MergeById => data.iter().position(|c| Some(c.id) == company.id),
+        };
+        let index = match index {
+            Some(index) => {
+                data[index].name = company.name.clone();
+                index
+            }
+            None => {
+                data.push(Company {
+                    id: fresh_id(company.id, &mut taken),
+                    name: company.name.clone(),
+                    servers: vec![],
+                });
+                data.len() - 1
+            }
+        };
+
+        for server in company.servers {
+            // By id a server may live in another company, by name only this one is searched
+            let found = match mode {
+                ImportMode::Replace => None,
+                ImportMode::MergeByNameHost => data[index]
+                    .servers
+                    .iter()
+                    .position(|s| s.name.eq_ignore_ascii_case(&server.name))
+                    .map(|i| (index, i)),
+                ImportMode::MergeById => data.iter().enumerate().find_map(|(ci, c)| {
+                    c.servers
+                        .iter()
+                        .position(|s| Some(s.id) == server.id)
+                        .map(|si| (ci, si))
+                }),
+            };
+
+            let target = match found {
+                Some((ci, si)) => {
+                    let existing = &mut data[ci].servers[si];
+                    existing.name = server.name;
+                    existing.description = server.description;
+                    existing.tags = server.tags;
+                    existing.favorite = server.favorite;
+                    (ci, si)
+                }
+                None => {
+                    data[index].servers.push(Server {
+                        id: fresh_id(server.id, &mut taken),
+                        name: server.name,
+                        description: server.description,
+                        services: vec![],
+                        tags: server.tags,
+                        favorite: server.favorite,
+                    });
+                    (index, data[index].servers.len() - 1)
+                }
+            };
+
+            if let ImportMode::Replace = mode {
+                // Everything is new, but a service that replaces one with the same id keeps
+                // its sensitive options unless the file may set them
+                for service in server.services {
+                    let id = fresh_id(service.id, &mut taken);
+                    let previous = find_service(current, id)
+                        .map(|(c, s, v)| &current[c].servers[s].services[v]);
+                    data[target.0].servers[target.1]
+                        .services
+                        .push(to_service(service, id, previous, apply_sensitive));
+                }
+            } else {
+                merge_services(&mut data, target, server.services, mode, &mut taken, apply_sensitive);
+            }
+        }
+    }
+
+    data
+}
+
+/// Checks the result of a merge, which may bring the same endpoint to a server twice.
+/// Fields are reported as `merged.companies[i].servers[j].services[k]`.
+pub fn validate_merged(data: &InnerCompanyServices) -> anyhow::Result<()> {
+    let mut validator = Validator::new();
+    for (i, company) in data.iter().enumerate() {
+        for (j, server) in company.servers.iter().enumerate() {
+            let endpoints: Vec<(Protocol, &str, i32)> = server
+                .services
+                .iter()
+                .map(|s| (s.protocol, s.host.as_str(), s.port))
+                .collect();
+            validator.unique_endpoints(&format!("merged.companies[{i}].servers[{j}]"), &endpoints);
+        }
+    }
+    validator.finish()
+}
+
+/// Lists what changes between `old` and `new`, matching entries by id.
+pub fn diff(
+    old: &InnerCompanyServices,
+    new: &InnerCompanyServices,
+    dry_run: bool,
+) -> InventoryDiff {
+    let mut changes = Vec::new();
+    let mut push = |kind, entity, id, name: String| {
+        changes.push(InventoryChange {
+            kind,
+            entity,
+            id,
+            name,
+        })
+    };
+
+    let old_servers: Vec<&Server<Service>> = old.iter().flat_map(|c| c.servers.iter()).collect();
+    let new_servers: Vec<&Server<Service>> = new.iter().flat_map(|c| c.servers.iter()).collect();
+    let old_services: Vec<&Service> = old_servers.iter().flat_map(|s| s.services.iter()).collect();
+    let new_services: Vec<&Service> = new_servers.iter().flat_map(|s| s.services.iter()).collect();
+
+    for company in new {
+        match old.iter().find(|c| c.id == company.id) {
+            None => push(
+                InventoryChangeKind::Added,
+                InventoryEntity::Company,
+                company.id,
+                company.name.clone(),
+            ),
+            Some(prev) if prev.name != company.name => push(
+                InventoryChangeKind::Updated,
+                InventoryEntity::Company,
+                company.id,
+                company.name.clone(),
+            ),
+            Some(_) => {}
+        }
+    }
+    for company in old.iter().filter(|c| !new.iter().any(|n| n.id == c.id)) {
+        push(
+            InventoryChangeKind::Removed,
+            InventoryEntity::Company,
+            company.id,
+            company.name.clone(),
+        );
+    }
+
+    for company in new {
+        for server in &company.servers {
+            let prev_company = old
+                .iter()
+                .find(|c| c.servers.iter().any(|s| s.id == server.id));
+            match old_servers.iter().find(|s| s.id == server.id) {
+                None => push(
+                    InventoryChangeKind::Added,
+                    InventoryEntity::Server,
+                    server.id,
+                    server.name.clone(),
+                ),
+                Some(prev)
+                    if prev.name != server.name
+                        || prev.description != server.description
+                        || prev.tags != server.tags
+                        || prev.favorite != server.favorite
+                        || prev_company.map(|c| c.id) != Some(company.id) =>
+                {
+                    push(
+                        InventoryChangeKind::Updated,
+                        InventoryEntity::Server,
+                        server.id,
+                        server.name.clone(),
+                    )
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    for server in old_servers
+        .iter()
+        .filter(|s| !new_servers.iter().any(|n| n.id == s.id))
+    {
+        push(
+            InventoryChangeKind::Removed,
+            InventoryEntity::Server,
+            server.id,
+            server.name.clone(),
+        );
+    }
+
+    for service in &new_services {
+        let name = format!("{}:{}", service.host, service.port);
+        match old_services.iter().find(|s| s.id == service.id) {
+            None => push(
+                InventoryChangeKind::Added,
+                InventoryEntity::Service,
+                service.id,
+                name,
+            ),
+            Some(prev) if prev != service => push(
+                InventoryChangeKind::Updated,
+                InventoryEntity::Service,
+                service.id,
+                name,
+            ),
+            Some(_) => {}
+        }
+    }
+    for service in old_services
+        .iter()
+        .filter(|s| !new_services.iter().any(|n| n.id == s.id))
+    {
+        let name = format!("{}:{}", service.host, service.port);
+        push(
+            InventoryChangeKind::Removed,
+            InventoryEntity::Service,
+            service.id,
+            name,
+        );
+    }
+
+    let count =
+        |kind: fn(&InventoryChangeKind) -> bool| changes.iter().filter(|c| kind(&c.kind)).count();
+    InventoryDiff {
+        dry_run,
+        added: count(|k| matches!(k, InventoryChangeKind::Added)),
+        updated: count(|k| matches!(k, InventoryChangeKind::Updated)),
+        removed: count(|k| matches!(k, InventoryChangeKind::Removed)),
+        changes,
+        sensitive: Vec::new(),
+        sensitive_applied: false,
+    }
+}
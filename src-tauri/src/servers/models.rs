@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use uuid::Uuid;
 
+use super::probe::{ReachabilityStatus, ServiceHealth};
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum Protocol {
@@ -18,6 +20,55 @@ impl Protocol {
     }
 }
 
+/// How the local endpoint of a service reaches its target.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessMode {
+    /// `cloudflared tunnel --url tcp://host:port`
+    #[default]
+    Tunnel,
+    /// `cloudflared access tcp --hostname <access_hostname>` for Access-protected applications
+    Access,
+}
+
+/// Shell commands run around a connection. Each receives `ARGO_*` environment variables.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+#[serde(default)]
+pub struct ConnectionHooks {
+    /// Runs before the tunnel starts; a non-zero exit aborts the connection
+    pub pre_connect: Option<String>,
+    /// Runs once the tunnel is ready, before the client is launched
+    pub post_connect: Option<String>,
+    /// Runs after the client exits and the tunnel is closed
+    pub post_disconnect: Option<String>,
+}
+
+/// Per-service connection options. Unset fields fall back to the global `Settings`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+#[serde(default)]
+pub struct ServiceOptions {
+    /// Disconnect after this many seconds without tunnel traffic (`0` disables)
+    pub idle_timeout_secs: Option<u64>,
+    pub hooks: ConnectionHooks,
+    pub access_mode: AccessMode,
+    /// Cloudflare Access application hostname, required for `AccessMode::Access`
+    pub access_hostname: Option<String>,
+    /// Local endpoint address, e.g. `0.0.0.0`, `192.168.1.10` or `::`; loopback when unset
+    pub bind_address: Option<String>,
+    /// Addresses or CIDR ranges allowed to connect besides loopback
+    pub allowed_sources: Vec<String>,
+    /// Login suggested when credentials are requested
+    pub default_login: Option<String>,
+    /// SSH private key path, e.g. from `IdentityFile`; used when no password is stored
+    pub identity_file: Option<String>,
+    /// SSH jump hosts, e.g. from `ProxyJump`. Kept for reference only: connections go
+    /// through cloudflared, which already reaches the host
+    pub proxy_jump: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct ErasedService {
@@ -25,7 +76,20 @@ pub struct ErasedService {
     pub protocol: Protocol,
     pub host: String,
     pub port: i32,
-    pub status: Option<String>,
+    /// Result of the last reachability check, `Unknown` until the first one
+    pub status: ReachabilityStatus,
+    pub options: ServiceOptions,
+    pub tags: Vec<String>,
+    pub favorite: bool,
+    /// Details of the last reachability check, filled in by `changes::erase`
+    pub health: Option<ServiceHealth>,
+}
+
+impl ErasedService {
+    pub fn set_health(&mut self, health: Option<ServiceHealth>) {
+        self.status = health.as_ref().map_or_else(Default::default, |h| h.status);
+        self.health = health;
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -34,13 +98,12 @@ pub struct Service {
     pub protocol: Protocol,
     pub port: i32,
     pub host: String,
-    pub status: Option<String>,
-}
-
-impl Service {
-    pub fn is_active(&self) -> bool {
-        self.status == Some("active".to_string())
-    }
+    #[serde(default)]
+    pub options: ServiceOptions,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub favorite: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
@@ -49,6 +112,10 @@ pub struct Server<S> {
     pub name: String,
     pub description: Option<String>,
     pub services: Vec<S>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub favorite: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
@@ -65,6 +132,22 @@ pub type LocalServersErased = Vec<Server<ErasedService>>;
 pub type ErasedServiceCompanies = Vec<Company<ErasedService>>;
 pub type InnerCompanyServices = Vec<Company<Service>>;
 
+impl From<Service> for ErasedService {
+    fn from(service: Service) -> Self {
+        ErasedService {
+            id: service.id,
+            protocol: service.protocol,
+            host: service.host,
+            port: service.port,
+            status: ReachabilityStatus::Unknown,
+            options: service.options,
+            tags: service.tags,
+            favorite: service.favorite,
+            health: None,
+        }
+    }
+}
+
 impl From<Company<Service>> for Company<ErasedService> {
     fn from(inner: Company<Service>) -> Self {
         let servers = inner
@@ -74,19 +157,15 @@ impl From<Company<Service>> for Company<ErasedService> {
                 let services = server
                     .services
                     .into_iter()
-                    .map(|service| ErasedService {
-                        id: service.id,
-                        protocol: service.protocol,
-                        host: service.host.clone(),
-                        port: service.port,
-                        status: service.status,
-                    })
+                    .map(ErasedService::from)
                     .collect();
                 Server {
                     id: server.id,
                     name: server.name,
                     description: server.description,
                     services,
+                    tags: server.tags,
+                    favorite: server.favorite,
                 }
             })
             .collect();
@@ -103,19 +182,15 @@ impl From<Server<Service>> for Server<ErasedService> {
         let services = inner
             .services
             .into_iter()
-            .map(|service| ErasedService {
-                id: service.id,
-                protocol: service.protocol,
-                host: service.host.clone(),
-                port: service.port,
-                status: service.status,
-            })
+            .map(ErasedService::from)
             .collect();
         Server {
             id: inner.id,
             name: inner.name,
             description: inner.description,
             services,
+            tags: inner.tags,
+            favorite: inner.favorite,
         }
     }
 }
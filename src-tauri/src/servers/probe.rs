@@ -0,0 +1,200 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::net::TcpStream;
+use tracing::{debug, warn};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use super::{
+    event::ServersEvent,
+    models::{AccessMode, Service},
+    ServersState, SERVERS_EVENT, UPDATE_SECONDS,
+};
+
+const CONNECT_TIMEOUT_SECS: u64 = 5;
+/// Reachable services slower than this are reported as degraded
+const DEGRADED_LATENCY_MS: u64 = 500;
+/// Latency changes smaller than this fraction are not worth an event
+const LATENCY_CHANGE_RATIO: f64 = 0.25;
+const PROBE_BATCH: usize = 32;
+const ACCESS_PORT: u16 = 443;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub enum ReachabilityStatus {
+    Online,
+    Offline,
+    /// Reachable, but slower than expected
+    Degraded,
+    /// Not checked yet
+    #[default]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export)]
+pub struct ServiceHealth {
+    pub service_id: Uuid,
+    pub status: ReachabilityStatus,
+    pub checked_at: Option<String>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl ServiceHealth {
+    /// Whether the change from `previous` should be reported to the UI.
+    fn differs(&self, previous: Option<&ServiceHealth>) -> bool {
+        let Some(previous) = previous else {
+            return true;
+        };
+        if previous.status != self.status {
+            return true;
+        }
+        match (previous.latency_ms, self.latency_ms) {
+            (Some(old), Some(new)) => {
+                (new as f64 - old as f64).abs() > old.max(1) as f64 * LATENCY_CHANGE_RATIO
+            }
+            (old, new) => old != new,
+        }
+    }
+}
+
+/// Address a connection goes through: cloudflared's loopback port while the service is
+/// connected, so the path through cloudflared is checked without going through the relay
+/// and its idle timer. Otherwise the Access application for `AccessMode::Access` or the
+/// service itself.
+fn target(service: &Service, tunnel: Option<SocketAddr>) -> Option<(String, u16)> {
+    if let Some(tunnel) = tunnel {
+        return Some((tunnel.ip().to_string(), tunnel.port()));
+    }
+    match service.options.access_mode {
+        AccessMode::Access => service
+            .options
+            .access_hostname
+            .as_deref()
+            .map(str::trim)
+            .filter(|h| !h.is_empty())
+            .map(|h| (h.to_string(), ACCESS_PORT)),
+        AccessMode::Tunnel => u16::try_from(service.port)
+            .ok()
+            .map(|port| (service.host.clone(), port)),
+    }
+}
+
+async fn probe(service: Service, tunnel: Option<SocketAddr>) -> ServiceHealth {
+    let mut health = ServiceHealth {
+        service_id: service.id,
+        checked_at: Some(Utc::now().to_rfc3339()),
+        ..Default::default()
+    };
+    let Some((host, port)) = target(&service, tunnel) else {
+        health.error = Some("no address to probe".to_string());
+        return health;
+    };
+
+    let started = Instant::now();
+    let result = tokio::time::timeout(
+        Duration::from_secs(CONNECT_TIMEOUT_SECS),
+        TcpStream::connect((host.as_str(), port)),
+    )
+    .await;
+
+    match result {
+        Ok(Ok(_)) => {
+            let latency = started.elapsed().as_millis() as u64;
+            health.latency_ms = Some(latency);
+            health.status = if latency > DEGRADED_LATENCY_MS {
+                ReachabilityStatus::Degraded
+            } else {
+                ReachabilityStatus::Online
+            };
+        }
+        Ok(Err(e)) => {
+            health.status = ReachabilityStatus::Offline;
+            health.error = Some(e.to_string());
+        }
+        Err(_) => {
+            health.status = ReachabilityStatus::Offline;
+            health.error = Some(format!("no answer within {CONNECT_TIMEOUT_SECS} s"));
+        }
+    }
+
+    debug!(
+        "probe {host}:{port} for service {}: {:?}",
+        service.id, health.status
+    );
+    health
+}
+
+/// Checks every service each `UPDATE_SECONDS` and emits `ServersEvent::Health`
+/// with the entries that changed.
+pub async fn watch(app: AppHandle) {
+    let mut interval = tokio::time::interval(Duration::from_secs(UPDATE_SECONDS));
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = check(&app).await {
+            warn!("reachability check failed: {e}");
+        }
+    }
+}
+
+async fn check(app: &AppHandle) -> anyhow::Result<()> {
+    let servers_state = app.state::<ServersState>();
+    let services: Vec<Service> = servers_state
+        .get_servers_data_mut()
+        .await
+        .iter()
+        .flat_map(|c| c.servers.iter())
+        .flat_map(|s| s.services.iter())
+        .cloned()
+        .collect();
+    let tunnels = crate::remote::tunnel_endpoints(app).await;
+
+    let mut results = Vec::with_capacity(services.len());
+    for batch in services.chunks(PROBE_BATCH) {
+        let handles: Vec<_> = batch
+            .iter()
+            .cloned()
+            .map(|service| {
+                let tunnel = tunnels.get(&service.id).copied();
+                tauri::async_runtime::spawn(probe(service, tunnel))
+            })
+            .collect();
+        for handle in handles {
+            results.push(handle.await?);
+        }
+    }
+
+    let ids: Vec<Uuid> = services.iter().map(|s| s.id).collect();
+    let changed = servers_state.update_health(results, &ids).await;
+    if !changed.is_empty() {
+        app.emit(SERVERS_EVENT, ServersEvent::Health(changed))?;
+    }
+
+    Ok(())
+}
+
+/// Stores fresh results and returns the ones worth reporting.
+pub(super) fn merge_health(
+    stored: &mut std::collections::HashMap<Uuid, ServiceHealth>,
+    results: Vec<ServiceHealth>,
+    known: &[Uuid],
+) -> Vec<ServiceHealth> {
+    stored.retain(|id, _| known.contains(id));
+
+    let mut changed = Vec::new();
+    for health in results {
+        if health.differs(stored.get(&health.service_id)) {
+            changed.push(health.clone());
+        }
+        stored.insert(health.service_id, health);
+    }
+    changed
+}
@@ -0,0 +1,212 @@
+//! Fuzzy search over the service list.
+//!
+//! Every whitespace-separated term of the query has to match at least one field of a
+//! service. A term scores highest as an exact match, then as a prefix, then as a
+//! substring and finally as a subsequence (`pgprd` in `pg-prod`). Field weights favour
+//! server names and hosts over descriptions; favorites get a small boost.
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use super::models::{InnerCompanyServices, Protocol};
+
+const DEFAULT_LIMIT: usize = 50;
+const FAVORITE_BONUS: f64 = 1.1;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub enum SearchField {
+    Company,
+    Server,
+    Description,
+    Host,
+    Port,
+    Protocol,
+    Tag,
+}
+
+impl SearchField {
+    fn weight(self) -> f64 {
+        match self {
+            SearchField::Server => 1.0,
+            SearchField::Host => 0.9,
+            SearchField::Tag => 0.8,
+            SearchField::Port | SearchField::Protocol => 0.7,
+            SearchField::Company => 0.6,
+            SearchField::Description => 0.5,
+        }
+    }
+}
+
+/// Matched character ranges (`[start, end)`, in characters) within one field value.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SearchHighlight {
+    pub field: SearchField,
+    pub value: String,
+    pub ranges: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SearchHit {
+    pub company_id: Uuid,
+    pub company: String,
+    pub server_id: Uuid,
+    pub server: String,
+    pub service_id: Uuid,
+    pub protocol: Protocol,
+    pub host: String,
+    pub port: i32,
+    pub favorite: bool,
+    pub score: f64,
+    pub highlights: Vec<SearchHighlight>,
+}
+
+/// Lowercases without changing the length, so match positions line up with the original value.
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Scores `term` against `value`; returns the score in `0..=1` and the matched character positions.
+fn match_term(term: &[char], value: &str) -> Option<(f64, Vec<usize>)> {
+    let value: Vec<char> = value.chars().map(lowercase).collect();
+    if term.is_empty() || value.is_empty() || term.len() > value.len() {
+        return None;
+    }
+
+    if value == term {
+        return Some((1.0, (0..term.len()).collect()));
+    }
+    if let Some(start) = value.windows(term.len()).position(|w| w == term) {
+        let positions = (start..start + term.len()).collect();
+        if start == 0 {
+            return Some((0.8, positions));
+        }
+        // Matches at a word boundary rank above ones in the middle of a word
+        let boundary = !value[start - 1].is_alphanumeric();
+        return Some((if boundary { 0.7 } else { 0.6 }, positions));
+    }
+
+    let mut positions = Vec::with_capacity(term.len());
+    let mut from = 0;
+    for c in term {
+        let offset = value[from..].iter().position(|v| v == c)?;
+        positions.push(from + offset);
+        from += offset + 1;
+    }
+    let span = positions.last()? - positions.first()? + 1;
+    // Tightly packed subsequences score close to a substring match
+    Some((0.4 * term.len() as f64 / span as f64, positions))
+}
+
+/// Collapses sorted character positions into `[start, end)` ranges.
+fn to_ranges(mut positions: Vec<usize>) -> Vec<(usize, usize)> {
+    positions.sort_unstable();
+    positions.dedup();
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for p in positions {
+        match ranges.last_mut() {
+            Some((_, end)) if *end == p => *end += 1,
+            _ => ranges.push((p, p + 1)),
+        }
+    }
+    ranges
+}
+
+pub fn search(data: &InnerCompanyServices, query: &str, limit: Option<usize>) -> Vec<SearchHit> {
+    let terms: Vec<Vec<char>> = query
+        .split_whitespace()
+        .map(|t| t.chars().map(lowercase).collect())
+        .collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+
+    let mut hits = Vec::new();
+    for company in data {
+        for server in &company.servers {
+            for service in &server.services {
+                let mut fields: Vec<(SearchField, String)> = vec![
+                    (SearchField::Company, company.name.clone()),
+                    (SearchField::Server, server.name.clone()),
+                    (SearchField::Host, service.host.clone()),
+                    (SearchField::Port, service.port.to_string()),
+                    (SearchField::Protocol, service.protocol.as_str().to_string()),
+                ];
+                if let Some(description) = &server.description {
+                    fields.push((SearchField::Description, description.clone()));
+                }
+                fields.extend(
+                    server
+                        .tags
+                        .iter()
+                        .chain(service.tags.iter())
+                        .map(|t| (SearchField::Tag, t.clone())),
+                );
+
+                let mut matched: Vec<Vec<usize>> = vec![Vec::new(); fields.len()];
+                let mut score = 0.0;
+                let mut all_terms = true;
+                for term in &terms {
+                    let best = fields
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, (field, value))| {
+                            match_term(term, value).map(|(s, p)| (i, s * field.weight(), p))
+                        })
+                        .max_by(|a, b| a.1.total_cmp(&b.1));
+                    match best {
+                        Some((i, s, positions)) => {
+                            score += s;
+                            matched[i].extend(positions);
+                        }
+                        None => {
+                            all_terms = false;
+                            break;
+                        }
+                    }
+                }
+                if !all_terms {
+                    continue;
+                }
+
+                let favorite = server.favorite || service.favorite;
+                if favorite {
+                    score *= FAVORITE_BONUS;
+                }
+
+                let highlights = fields
+                    .into_iter()
+                    .zip(matched)
+                    .filter(|(_, positions)| !positions.is_empty())
+                    .map(|((field, value), positions)| SearchHighlight {
+                        field,
+                        value,
+                        ranges: to_ranges(positions),
+                    })
+                    .collect();
+
+                hits.push(SearchHit {
+                    company_id: company.id,
+                    company: company.name.clone(),
+                    server_id: server.id,
+                    server: server.name.clone(),
+                    service_id: service.id,
+                    protocol: service.protocol,
+                    host: service.host.clone(),
+                    port: service.port,
+                    favorite,
+                    score: score / terms.len() as f64,
+                    highlights,
+                });
+            }
+        }
+    }
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit.unwrap_or(DEFAULT_LIMIT));
+    hits
+}
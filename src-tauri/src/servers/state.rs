@@ -10,10 +10,13 @@ use tracing::{debug, warn};
 use uuid::Uuid;
 
 use super::{
-    credentials::{Credential, ServiceCredential},
+    changes::{Journal, ServersRevision, ServersSync},
+    credentials::{AccessServiceToken, Credential, ServiceCredential},
     models::{ErasedServiceCompanies, InnerCompanyServices, Service},
+    probe::{self, ServiceHealth},
 };
 use crate::activity::{ActivityState, event::{ActivityEventType, ActivitySeverity}};
+use crate::migrations;
 use tauri::Manager;
 
 const SERVERS_STORE: &str = "servers.json";
@@ -26,6 +29,10 @@ pub struct ServersState {
     servers_data: Arc<Mutex<InnerCompanyServices>>,
     expanded_companies: Arc<Mutex<HashSet<Uuid>>>,
     credentials: Arc<Mutex<HashMap<Uuid, ServiceCredential>>>,
+    health: Arc<Mutex<HashMap<Uuid, ServiceHealth>>>,
+    journal: Arc<Mutex<Journal>>,
+    /// Учетные данные последнего предпросмотра импорта по `ImportedService::credential_id`
+    import_credentials: Arc<Mutex<HashMap<Uuid, Credential>>>,
 }
 
 impl ServersState {
@@ -47,8 +54,11 @@ impl ServersState {
         Ok(Self {
             app,
             expanded_companies: Arc::new(Mutex::new(expanded_companies)),
+            journal: Arc::new(Mutex::new(Journal::new(servers_data.clone()))),
             servers_data: Arc::new(Mutex::new(servers_data)),
             credentials: Arc::default(),
+            health: Arc::default(),
+            import_credentials: Arc::default(),
         })
     }
 
@@ -64,6 +74,10 @@ impl ServersState {
             SERVERS_DATA_KEY,
             serde_json::to_value(InnerCompanyServices::clone(&servers_data))?,
         );
+        store.set(
+            migrations::SCHEMA_VERSION_KEY,
+            serde_json::json!(migrations::SERVERS.version),
+        );
         store.save()?;
 
         Ok(())
@@ -95,9 +109,61 @@ impl ServersState {
         result
     }
 
-    pub async fn get_data(&self) -> ErasedServiceCompanies {
+    /// Избранные серверы и сервисы в порядке списка
+    pub async fn list_favorites(&self) -> (Vec<Uuid>, Vec<Uuid>) {
+        let data = self.servers_data.lock().await;
+        let servers = data.iter().flat_map(|c| c.servers.iter());
+        (
+            servers.clone().filter(|s| s.favorite).map(|s| s.id).collect(),
+            servers
+                .flat_map(|s| s.services.iter())
+                .filter(|s| s.favorite)
+                .map(|s| s.id)
+                .collect(),
+        )
+    }
+
+    /// Опубликованное дерево вместе с его ревизией
+    pub async fn get_snapshot(&self) -> (u64, ErasedServiceCompanies) {
+        let journal = self.journal.lock().await;
+        let health = self.health.lock().await;
+        journal.snapshot(&health)
+    }
+
+    /// Фиксирует изменения с последней публикации как новую ревизию
+    pub async fn publish_changes(&self) -> Option<ServersRevision> {
         let data = self.servers_data.lock().await;
-        data.clone().into_iter().map(|d| d.into()).collect()
+        let mut journal = self.journal.lock().await;
+        let health = self.health.lock().await;
+        journal.publish(&data, &health)
+    }
+
+    /// Ревизии после `revision` или полный снимок, если журнал их уже не хранит
+    pub async fn get_since(&self, revision: Option<u64>) -> ServersSync {
+        let journal = self.journal.lock().await;
+        let health = self.health.lock().await;
+        journal.since(revision, &health)
+    }
+
+    /// Последние результаты проверки доступности; `None` — по всем сервисам
+    pub async fn list_health(&self, service_id: Option<Uuid>) -> Vec<ServiceHealth> {
+        self.health
+            .lock()
+            .await
+            .values()
+            .filter(|h| service_id.is_none_or(|id| h.service_id == id))
+            .cloned()
+            .collect()
+    }
+
+    /// Сохраняет результаты проверки и возвращает изменившиеся
+    pub async fn update_health(
+        &self,
+        results: Vec<ServiceHealth>,
+        known: &[Uuid],
+    ) -> Vec<ServiceHealth> {
+        let mut health = self.health.lock().await;
+        probe::merge_health(&mut health, results, known)
     }
 
     pub async fn update(&self, data: InnerCompanyServices) -> anyhow::Result<bool> {
@@ -258,6 +324,134 @@ impl ServersState {
         Ok(cred.credential.clone())
     }
 
+    /// Запоминает учетные данные нового предпросмотра импорта вместо предыдущих
+    pub async fn hold_import_credentials(&self, credentials: HashMap<Uuid, Credential>) {
+        *self.import_credentials.lock().await = credentials;
+    }
+
+    /// Отдает запомненные учетные данные импорта; после применения ничего не остается
+    pub async fn take_import_credentials(&self) -> HashMap<Uuid, Credential> {
+        std::mem::take(&mut *self.import_credentials.lock().await)
+    }
+
+    /// Удаляет секреты удаленных сервисов: учетные данные из кэша и хранилища и service
+    /// token Cloudflare Access. Ошибки keyring только логируем — сервисов уже нет
+    pub async fn forget_services(&self, ids: &[Uuid]) {
+        let mut credentials = self.credentials.lock().await;
+        for id in ids {
+            credentials.remove(id);
+            if let Err(e) = ServiceCredential::delete(*id) {
+                warn!("cannot delete stored credential of service {id}: {e:#}");
+            }
+            if let Err(e) = AccessServiceToken::delete(*id) {
+                warn!("cannot delete Access service token of service {id}: {e:#}");
+            }
+        }
+    }
+
+    /// Сохраняет импортированные учетные данные только что добавленного сервиса в keyring
+    pub async fn import_service_credential(
+        &self,
+        service: &Service,
+        credential: Credential,
+    ) -> anyhow::Result<()> {
+        if credential.is_empty() {
+            return Ok(());
+        }
+
+        let mut cred = ServiceCredential::new(service)?;
+        cred.credential = credential;
+        cred.remember()?;
+        self.credentials.lock().await.insert(service.id, cred);
+
+        if let Some(activity_state) = self.app.try_state::<ActivityState>() {
+            let _ = activity_state
+                .add_event(
+                    ActivityEventType::CredentialsSaved,
+                    format!("Учетные данные для сервиса {} импортированы", service.id),
+                    Some(serde_json::json!({
+                        "service_id": service.id,
+                        "protocol": service.protocol.as_str(),
+                        "imported": true
+                    })),
+                    Some(service.id),
+                    None,
+                    None,
+                    ActivitySeverity::Info,
+                )
+                .await;
+        }
+
+        Ok(())
+    }
+
+    pub async fn has_access_service_token(&self, id: Uuid) -> anyhow::Result<bool> {
+        Ok(AccessServiceToken::load(id)?.is_some())
+    }
+
+    pub async fn set_access_service_token(
+        &self,
+        id: Uuid,
+        client_id: String,
+        client_secret: String,
+    ) -> anyhow::Result<()> {
+        self.get_service(id)
+            .await
+            .ok_or(anyhow::anyhow!("no service with id: {id}"))?;
+
+        if client_id.trim().is_empty() || client_secret.trim().is_empty() {
+            anyhow::bail!("both client id and client secret are required");
+        }
+
+        AccessServiceToken {
+            client_id: client_id.trim().to_string(),
+            client_secret: client_secret.trim().to_string(),
+        }
+        .save(id)?;
+
+        if let Some(activity_state) = self.app.try_state::<ActivityState>() {
+            let _ = activity_state
+                .add_event(
+                    ActivityEventType::CredentialsSaved,
+                    format!("Service token Cloudflare Access для сервиса {} сохранен", id),
+                    Some(serde_json::json!({
+                        "service_id": id,
+                        "kind": "access_service_token"
+                    })),
+                    Some(id),
+                    None,
+                    None,
+                    ActivitySeverity::Info,
+                )
+                .await;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_access_service_token(&self, id: Uuid) -> anyhow::Result<()> {
+        AccessServiceToken::delete(id)?;
+
+        if let Some(activity_state) = self.app.try_state::<ActivityState>() {
+            let _ = activity_state
+                .add_event(
+                    ActivityEventType::CredentialsDeleted,
+                    format!("Service token Cloudflare Access для сервиса {} удален", id),
+                    Some(serde_json::json!({
+                        "service_id": id,
+                        "kind": "access_service_token"
+                    })),
+                    Some(id),
+                    None,
+                    None,
+                    ActivitySeverity::Info,
+                )
+                .await;
+        }
+
+        Ok(())
+    }
+
     pub async fn save_servers(&self) -> anyhow::Result<()> {
         self.save().await
     }
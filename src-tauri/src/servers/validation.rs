@@ -0,0 +1,368 @@
+//! Input checks shared by the server and service commands.
+//!
+//! A `Validator` collects every problem instead of stopping at the first one, so a form can
+//! mark all invalid inputs at once. Commands turn the result into an error with
+//! `command_error`, which sends `ValidationErrors` to the UI as JSON; `field` is the command
+//! argument name or a path such as `candidates[0].services[1].port` for nested input.
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use super::models::{AccessMode, Protocol, Service, ServiceOptions};
+
+pub const NAME_MAX_CHARS: usize = 100;
+pub const DESCRIPTION_MAX_CHARS: usize = 1000;
+pub const TAG_MAX_CHARS: usize = 50;
+const PATH_MAX_CHARS: usize = 4096;
+const HOSTNAME_MAX_CHARS: usize = 253;
+const LABEL_MAX_CHARS: usize = 63;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub enum ValidationCode {
+    Required,
+    TooLong,
+    InvalidCharacters,
+    InvalidHost,
+    InvalidPort,
+    Duplicate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FieldError {
+    pub field: String,
+    pub code: ValidationCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self
+            .errors
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect();
+        write!(f, "{}", errors.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// Error string for a command: validation failures as JSON, everything else as text.
+pub fn command_error(e: anyhow::Error) -> String {
+    match e.downcast_ref::<ValidationErrors>() {
+        Some(errors) => serde_json::to_string(errors).unwrap_or_else(|_| errors.to_string()),
+        None => e.to_string(),
+    }
+}
+
+/// `prefix.field`, or just `field` without a prefix.
+pub fn path(prefix: &str, field: &str) -> String {
+    if prefix.is_empty() {
+        field.to_string()
+    } else {
+        format!("{prefix}.{field}")
+    }
+}
+
+fn is_hostname(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    if host.is_empty() || host.chars().count() > HOSTNAME_MAX_CHARS {
+        return false;
+    }
+
+    let labels: Vec<&str> = host.split('.').collect();
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= LABEL_MAX_CHARS
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            // Underscores are not allowed by RFC 1123, but are common in NetBIOS-era names
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    });
+    // `300.1.1.1` is a broken address rather than a name, and top-level domains are never numeric
+    let numeric_tld = labels
+        .last()
+        .is_some_and(|l| l.chars().all(|c| c.is_ascii_digit()));
+
+    valid_labels && !numeric_tld
+}
+
+/// Accepts hostnames, IPv4 and IPv6 addresses, the latter optionally in brackets.
+pub fn is_valid_host(host: &str) -> bool {
+    let unbracketed = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+
+    unbracketed.parse::<Ipv6Addr>().is_ok()
+        || host.parse::<Ipv4Addr>().is_ok()
+        || (unbracketed == host && is_hostname(host))
+}
+
+/// Checks one `ProxyJump` hop: `[ssh://][user@]host[:port]`, IPv6 hosts in brackets.
+fn is_valid_jump(jump: &str) -> bool {
+    let jump = jump.strip_prefix("ssh://").unwrap_or(jump);
+    let (user, target) = jump.rsplit_once('@').unwrap_or(("", jump));
+    if user.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        return false;
+    }
+    let (host, port) = match target.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((host, "")) => (host, None),
+            Some((host, port)) => match port.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => return false,
+            },
+            None => return false,
+        },
+        None => match target.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (target, None),
+        },
+    };
+    let valid_port = port.is_none_or(|p| p.parse::<u16>().is_ok_and(|p| p != 0));
+
+    valid_port && !host.contains('[') && is_valid_host(host)
+}
+
+/// Form of the host used to compare endpoints: lowercase, without brackets and trailing dot.
+fn host_key(host: &str) -> String {
+    let host = host.trim();
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    let host = host.strip_suffix('.').unwrap_or(host);
+    match host.parse::<Ipv6Addr>() {
+        Ok(ip) => ip.to_string(),
+        Err(_) => host.to_ascii_lowercase(),
+    }
+}
+
+fn display_endpoint(protocol: Protocol, host: &str, port: i32) -> String {
+    let host = host.trim();
+    if host.contains(':') && !host.starts_with('[') {
+        format!("{} service [{host}]:{port}", protocol.as_str())
+    } else {
+        format!("{} service {host}:{port}", protocol.as_str())
+    }
+}
+
+pub fn same_endpoint(a: (Protocol, &str, i32), b: (Protocol, &str, i32)) -> bool {
+    a.0 == b.0 && a.2 == b.2 && host_key(a.1) == host_key(b.1)
+}
+
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn error(&mut self, field: String, code: ValidationCode, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field,
+            code,
+            message: message.into(),
+        });
+    }
+
+    fn text(&mut self, field: String, value: &str, required: bool, max_chars: usize) {
+        let value = value.trim();
+        if value.is_empty() {
+            if required {
+                self.error(field, ValidationCode::Required, "must not be empty");
+            }
+        } else if value.chars().count() > max_chars {
+            self.error(
+                field,
+                ValidationCode::TooLong,
+                format!("must be at most {max_chars} characters"),
+            );
+        } else if value.chars().any(char::is_control) {
+            self.error(
+                field,
+                ValidationCode::InvalidCharacters,
+                "must not contain control characters",
+            );
+        }
+    }
+
+    /// Company and server names: non-empty after trimming, at most `NAME_MAX_CHARS`.
+    pub fn name(&mut self, field: impl Into<String>, value: &str) -> &mut Self {
+        self.text(field.into(), value, true, NAME_MAX_CHARS);
+        self
+    }
+
+    pub fn description(&mut self, field: impl Into<String>, value: Option<&str>) -> &mut Self {
+        if let Some(value) = value {
+            // Line breaks are fine in a description
+            let value = value.replace(['\n', '\r', '\t'], " ");
+            self.text(field.into(), &value, false, DESCRIPTION_MAX_CHARS);
+        }
+        self
+    }
+
+    /// Every tag at most `TAG_MAX_CHARS`; empty tags are fine, they are dropped on save.
+    pub fn tags(&mut self, field: &str, tags: &[String]) -> &mut Self {
+        for (i, tag) in tags.iter().enumerate() {
+            self.text(format!("{field}[{i}]"), tag, false, TAG_MAX_CHARS);
+        }
+        self
+    }
+
+    /// Options passed on to the relay and the SSH client as they are.
+    pub fn service_options(&mut self, prefix: &str, options: &ServiceOptions) -> &mut Self {
+        // `cloudflared access tcp` cannot start without the Access application hostname
+        if options.access_mode == AccessMode::Access {
+            self.host(
+                path(prefix, "access_hostname"),
+                options.access_hostname.as_deref().unwrap_or_default(),
+            );
+        }
+
+        if let Some(bind) = options.bind_address.as_deref().map(str::trim) {
+            let ip = bind.trim_start_matches('[').trim_end_matches(']');
+            if !bind.is_empty() && ip.parse::<IpAddr>().is_err() {
+                self.error(
+                    path(prefix, "bind_address"),
+                    ValidationCode::InvalidHost,
+                    format!("{bind:?} is not an IPv4 or IPv6 address"),
+                );
+            }
+        }
+
+        if let Some(proxy_jump) = options.proxy_jump.as_deref().map(str::trim) {
+            let invalid = proxy_jump
+                .split(',')
+                .map(str::trim)
+                .find(|jump| !proxy_jump.is_empty() && !is_valid_jump(jump));
+            if let Some(jump) = invalid {
+                self.error(
+                    path(prefix, "proxy_jump"),
+                    ValidationCode::InvalidHost,
+                    format!("{jump:?} is not a valid [user@]host[:port] jump host"),
+                );
+            }
+        }
+
+        if let Some(identity_file) = options.identity_file.as_deref() {
+            self.text(
+                path(prefix, "identity_file"),
+                identity_file,
+                false,
+                PATH_MAX_CHARS,
+            );
+        }
+        self
+    }
+
+    pub fn host(&mut self, field: impl Into<String>, value: &str) -> &mut Self {
+        let field = field.into();
+        let value = value.trim();
+        if value.is_empty() {
+            self.error(field, ValidationCode::Required, "must not be empty");
+        } else if !is_valid_host(value) {
+            self.error(
+                field,
+                ValidationCode::InvalidHost,
+                format!("{value:?} is not a valid hostname, IPv4 or IPv6 address"),
+            );
+        }
+        self
+    }
+
+    pub fn port(&mut self, field: impl Into<String>, value: i32) -> &mut Self {
+        if !(1..=u16::MAX as i32).contains(&value) {
+            self.error(
+                field.into(),
+                ValidationCode::InvalidPort,
+                format!("{value} is not in 1..=65535"),
+            );
+        }
+        self
+    }
+
+    /// Host and port of a service, with fields under `prefix`.
+    pub fn endpoint(&mut self, prefix: &str, host: &str, port: i32) -> &mut Self {
+        self.host(path(prefix, "host"), host)
+            .port(path(prefix, "port"), port)
+    }
+
+    /// `protocol`/`host`/`port` must not repeat among `services`, except the service `except`.
+    pub fn unique_endpoint<'a>(
+        &mut self,
+        field: impl Into<String>,
+        endpoint: (Protocol, &str, i32),
+        services: impl IntoIterator<Item = &'a Service>,
+        except: Option<Uuid>,
+    ) -> &mut Self {
+        let duplicate = services.into_iter().any(|s| {
+            Some(s.id) != except && same_endpoint((s.protocol, &s.host, s.port), endpoint)
+        });
+        if duplicate {
+            let (protocol, host, port) = endpoint;
+            self.error(
+                field.into(),
+                ValidationCode::Duplicate,
+                format!(
+                    "{} already exists on this server",
+                    display_endpoint(protocol, host, port)
+                ),
+            );
+        }
+        self
+    }
+
+    /// Reports every repeated endpoint in a list, at the later occurrence.
+    pub fn unique_endpoints(
+        &mut self,
+        prefix: &str,
+        endpoints: &[(Protocol, &str, i32)],
+    ) -> &mut Self {
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            if endpoints[..i].iter().any(|e| same_endpoint(*e, *endpoint)) {
+                let (protocol, host, port) = endpoint;
+                self.error(
+                    path(prefix, &format!("services[{i}]")),
+                    ValidationCode::Duplicate,
+                    format!(
+                        "{} is listed more than once",
+                        display_endpoint(*protocol, host, *port)
+                    ),
+                );
+            }
+        }
+        self
+    }
+
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors {
+                errors: std::mem::take(&mut self.errors),
+            }
+            .into())
+        }
+    }
+}
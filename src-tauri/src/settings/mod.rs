@@ -6,19 +6,47 @@ use config::{Config, File, FileFormat};
 use json_patch::{patch, Patch};
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter, Manager};
+use tracing::error;
 
 use ts_rs::TS;
 
-use crate::util::{invoke, AppHandleExt, PanicLock};
+use crate::migrations;
+use crate::servers::ConnectionHooks;
+use crate::util::{build_http_client, invoke, set_http_client, AppHandleExt, PanicLock};
 use crate::activity::{ActivityState, event::{ActivityEventType, ActivitySeverity}};
 
 pub const SETTINGS_CHANGE_EVENT: &str = "config_change";
 const CONFIG_FILE: &str = "config.json";
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ProxySettings {
+    /// `http://`, `https://` or `socks5://` proxy URL, credentials may be embedded
+    pub url: String,
+    /// Comma-separated hosts, domains and CIDRs that bypass the proxy
+    pub no_proxy: Option<String>,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct Settings {
     pub remember_me: Option<bool>,
+    /// Global idle timeout for tunnels in seconds, overridden per service
+    pub idle_timeout_secs: Option<u64>,
+    /// How long before an idle disconnect the UI gets a warning
+    pub idle_warning_secs: Option<u64>,
+    /// Hooks run for every service, before the service's own hooks
+    pub hooks: Option<ConnectionHooks>,
+    /// Base URL of an internal cloudflared mirror used instead of GitHub
+    pub cloudflared_mirror_url: Option<String>,
+    /// Install cloudflared updates automatically while no tunnel is active
+    pub cloudflared_auto_update: Option<bool>,
+    /// Proxy used for all outbound HTTP requests
+    pub proxy: Option<ProxySettings>,
+    /// PEM files with additional trusted CA certificates
+    pub extra_ca_certificates: Option<Vec<String>>,
+    /// Disables certificate validation; off unless explicitly enabled
+    pub accept_invalid_certs: Option<bool>,
 }
 
 impl Settings {
@@ -26,7 +54,9 @@ impl Settings {
         let user_settings = app.path().config_dir()?.join(CONFIG_FILE);
 
         if !user_settings.try_exists().unwrap_or(false) {
-            fs::write(&user_settings, "{}")?;
+            let mut empty = serde_json::json!({});
+            migrations::stamp(&mut empty, &migrations::SETTINGS);
+            fs::write(&user_settings, empty.to_string())?;
         }
 
         let settings: Settings = Config::builder()
@@ -48,7 +78,9 @@ impl Settings {
     fn save(&self, app: &AppHandle) -> anyhow::Result<()> {
         let user_settings = app.path().config_dir()?.join(CONFIG_FILE);
 
-        fs::write(&user_settings, serde_json::to_string(self)?)?;
+        let mut value = serde_json::to_value(self)?;
+        migrations::stamp(&mut value, &migrations::SETTINGS);
+        fs::write(&user_settings, serde_json::to_string(&value)?)?;
 
         Ok(())
     }
@@ -58,6 +90,11 @@ pub type SettingsState = RwLock<Settings>;
 
 pub fn setup(app: &AppHandle) -> anyhow::Result<()> {
     let settings = Settings::new(app)?;
+    // Битые настройки прокси или сертификатов не должны мешать запуску: остается клиент по умолчанию
+    match build_http_client(&settings) {
+        Ok(client) => set_http_client(client),
+        Err(e) => error!("Cannot apply network settings, using the default HTTP client: {e:#}"),
+    }
 
     app.manage(SettingsState::new(settings));
 
@@ -74,11 +111,13 @@ pub fn setup(app: &AppHandle) -> anyhow::Result<()> {
 pub async fn update_settings(app: AppHandle, new_settings: Settings) -> Result<(), String> {
     async fn inner(app: AppHandle, new_settings: Settings) -> anyhow::Result<()> {
         let state = app.state::<SettingsState>();
+        let client = build_http_client(&new_settings)?;
         {
             let mut settings = state.write().unwrap();
             *settings = new_settings;
             settings.save(&app)?;
         } // Drop the guard here
+        set_http_client(client);
 
         let settings_clone = {
             let settings = state.read().unwrap();
@@ -121,7 +160,9 @@ pub async fn patch_settings(app: AppHandle, patch_doc: Patch) -> Result<(), Stri
 
             patch(&mut settings_doc, &patch_doc).map_err(anyhow::Error::new)?;
 
-            *settings = serde_json::from_value(settings_doc)?;
+            let patched: Settings = serde_json::from_value(settings_doc)?;
+            set_http_client(build_http_client(&patched)?);
+            *settings = patched;
             settings.save(&app)?;
         } // Drop the guard here
 
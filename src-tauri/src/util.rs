@@ -1,11 +1,18 @@
 use std::{
+    fs,
     hash::{DefaultHasher, Hash, Hasher},
+    io::Write,
+    path::Path,
     sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard},
 };
 
+use anyhow::Context;
+use once_cell::sync::Lazy;
 use tauri::{AppHandle, Event, Listener, Manager};
 use serde::{Deserialize, Serialize};
 
+use crate::settings::Settings;
+
 pub trait AppHandleExt {
     fn listen_async<H, F>(&self, event: &str, handler: H)
     where
@@ -32,12 +39,75 @@ impl AppHandleExt for AppHandle {
     }
 }
 
-pub fn make_reqwest() -> reqwest::Client {
-    reqwest::ClientBuilder::new()
-        .redirect(reqwest::redirect::Policy::none())
-        .danger_accept_invalid_certs(true)
-        .build()
-        .expect("cannot create reqwest client")
+static HTTP_CLIENT: Lazy<RwLock<reqwest::Client>> = Lazy::new(|| {
+    RwLock::new(
+        build_http_client(&Settings::default()).expect("cannot create reqwest client"),
+    )
+});
+
+/// Always bypass the proxy, so local services such as the KeePass container API
+/// never receive requests (and credentials) through it.
+const LOCAL_NO_PROXY: &str = "localhost,127.0.0.1,::1";
+
+/// Client shared by every outbound request, configured from `Settings`.
+pub fn http_client() -> reqwest::Client {
+    HTTP_CLIENT.readp().clone()
+}
+
+pub fn set_http_client(client: reqwest::Client) {
+    *HTTP_CLIENT.writep() = client;
+}
+
+/// Builds a client with the proxy and extra CA certificates from `settings`.
+/// Certificates are validated unless `accept_invalid_certs` is explicitly enabled.
+pub fn build_http_client(settings: &Settings) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::ClientBuilder::new()
+        .danger_accept_invalid_certs(settings.accept_invalid_certs.unwrap_or(false));
+
+    if let Some(proxy) = settings.proxy.as_ref().filter(|p| !p.url.trim().is_empty()) {
+        let mut no_proxy = LOCAL_NO_PROXY.to_string();
+        if let Some(extra) = proxy.no_proxy.as_deref().filter(|n| !n.trim().is_empty()) {
+            no_proxy = format!("{no_proxy},{}", extra.trim());
+        }
+        let no_proxy = reqwest::NoProxy::from_string(&no_proxy);
+        let proxy = reqwest::Proxy::all(proxy.url.trim())
+            .with_context(|| format!("invalid proxy url: {}", proxy.url))?
+            .no_proxy(no_proxy);
+        builder = builder.proxy(proxy);
+    }
+
+    for path in settings.extra_ca_certificates.iter().flatten() {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("cannot read CA certificate file {path}"))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("invalid PEM in {path}"))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Writes `content` to a temporary file next to `path` and renames it into place,
+/// so an interrupted write never leaves a truncated file behind.
+pub fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = (|| -> std::io::Result<()> {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
 }
 
 macro_rules! invoke {